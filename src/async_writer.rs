@@ -1,22 +1,218 @@
+use crate::config::AsyncWriterConfig;
+
+use crossbeam_channel::{Receiver, Sender, TrySendError, bounded};
+use std::fmt;
 use std::io;
-use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::Metadata;
+use tracing_subscriber::fmt::{MakeWriter, writer::BoxMakeWriter};
+
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+static DROPPED_LINES: AtomicU64 = AtomicU64::new(0);
+
+/// Lines dropped by every lossy [`AsyncMakeWriter`] since startup because its buffer
+/// was full.
+pub fn dropped_lines() -> u64 {
+    DROPPED_LINES.load(Ordering::Relaxed)
+}
+
+enum Message {
+    Line(Vec<u8>),
+    Flush(Sender<()>),
+    Shutdown,
+}
 
 #[derive(Clone)]
 pub struct AsyncMakeWriter {
-    writer: NonBlocking,
+    sender: Sender<Message>,
+    lossy: bool,
+    dropped: Arc<AtomicU64>,
 }
 
 impl AsyncMakeWriter {
-    pub fn new() -> (Self, WorkerGuard) {
-        let (non_blocking, guard) = tracing_appender::non_blocking(io::stdout());
-        (Self { writer: non_blocking }, guard)
+    pub fn new() -> (Self, AsyncWriterGuard) {
+        Self::from_writer(io::stdout())
+    }
+
+    pub fn from_writer<W>(writer: W) -> (Self, AsyncWriterGuard)
+    where
+        W: io::Write + Send + 'static,
+    {
+        Self::with_config(writer, &AsyncWriterConfig::default())
+    }
+
+    pub fn with_config<W>(writer: W, config: &AsyncWriterConfig) -> (Self, AsyncWriterGuard)
+    where
+        W: io::Write + Send + 'static,
+    {
+        let (sender, receiver) = bounded(config.buffered_lines_limit.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let handle = thread::Builder::new()
+            .name("baad-log-writer".to_string())
+            .spawn(move || run_worker(writer, receiver))
+            .expect("failed to spawn log writer thread");
+
+        let guard = AsyncWriterGuard {
+            sender: sender.clone(),
+            handle: Some(handle),
+            dropped: dropped.clone(),
+        };
+
+        let make_writer = Self {
+            sender,
+            lossy: config.lossy,
+            dropped,
+        };
+        (make_writer, guard)
+    }
+
+    /// Lines this writer has dropped because its buffer was full.
+    pub fn dropped_lines(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+fn run_worker<W: io::Write>(mut writer: W, receiver: Receiver<Message>) {
+    for message in receiver {
+        match message {
+            Message::Line(line) => {
+                let _ = writer.write_all(&line);
+            }
+            Message::Flush(ack) => {
+                let _ = writer.flush();
+                let _ = ack.send(());
+            }
+            Message::Shutdown => break,
+        }
+    }
+
+    let _ = writer.flush();
+}
+
+impl<'a> MakeWriter<'a> for AsyncMakeWriter {
+    type Writer = AsyncWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        AsyncWriter {
+            sender: self.sender.clone(),
+            lossy: self.lossy,
+            dropped: self.dropped.clone(),
+        }
+    }
+}
+
+pub struct AsyncWriter {
+    sender: Sender<Message>,
+    lossy: bool,
+    dropped: Arc<AtomicU64>,
+}
+
+impl io::Write for AsyncWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = Message::Line(buf.to_vec());
+
+        if !self.lossy {
+            return match self.sender.send(line) {
+                Ok(()) => Ok(buf.len()),
+                Err(_) => Err(io::ErrorKind::BrokenPipe.into()),
+            };
+        }
+
+        match self.sender.try_send(line) {
+            Ok(()) => Ok(buf.len()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                DROPPED_LINES.fetch_add(1, Ordering::Relaxed);
+                Ok(buf.len())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Owns the worker thread behind an [`AsyncMakeWriter`]. Dropping it drains every
+/// queued line into the target writer before returning.
+pub struct AsyncWriterGuard {
+    sender: Sender<Message>,
+    handle: Option<JoinHandle<()>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl AsyncWriterGuard {
+    pub fn dropped_lines(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Blocks until every line queued before this call has been written and the
+    /// target writer has been flushed.
+    pub fn flush(&self) {
+        let (ack, done) = bounded(1);
+        if self
+            .sender
+            .send_timeout(Message::Flush(ack), FLUSH_TIMEOUT)
+            .is_ok()
+        {
+            let _ = done.recv_timeout(FLUSH_TIMEOUT);
+        }
+    }
+}
+
+impl Drop for AsyncWriterGuard {
+    fn drop(&mut self) {
+        let sent = self
+            .sender
+            .send_timeout(Message::Shutdown, FLUSH_TIMEOUT)
+            .is_ok();
+
+        if let Some(handle) = self.handle.take()
+            && sent
+        {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedMakeWriter {
+    inner: Arc<BoxMakeWriter>,
+}
+
+impl SharedMakeWriter {
+    pub fn new<M>(make_writer: M) -> Self
+    where
+        M: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        Self {
+            inner: Arc::new(BoxMakeWriter::new(make_writer)),
+        }
     }
 }
 
-impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for AsyncMakeWriter {
-    type Writer = NonBlocking;
+impl fmt::Debug for SharedMakeWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedMakeWriter")
+            .field(&self.inner)
+            .finish()
+    }
+}
+
+impl<'a> MakeWriter<'a> for SharedMakeWriter {
+    type Writer = Box<dyn io::Write + 'a>;
 
     fn make_writer(&'a self) -> Self::Writer {
-        self.writer.clone()
+        self.inner.make_writer()
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.inner.make_writer_for(meta)
     }
 }
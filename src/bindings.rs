@@ -13,23 +13,510 @@
 //! This module exists solely to provide UniFFI-compatible wrappers that convert between
 //! Rust types and UniFFI-compatible types (e.g., `PathBuf` → `String`, async → sync).
 
-use std::collections::HashMap;
+use crate::context::{ContextGuard, LogContext};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
-use std::sync::OnceLock;
-use tracing_appender::non_blocking::WorkerGuard;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tracing_subscriber::fmt::format::FmtSpan;
 
+pub use crate::config::LogRotation;
+pub use crate::config::{OtlpProtocol, RedactionConfig};
+pub use crate::config_loader::ConfigSource;
 pub use crate::error::{ConfigError, FileError};
+pub use crate::summary::LogCounts;
+
+static LOGGER_GUARD: Mutex<Option<crate::config::LoggingGuard>> = Mutex::new(None);
 
-static LOGGER_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
+fn store_guard(guard: crate::config::LoggingGuard) {
+    *LOGGER_GUARD.lock().unwrap_or_else(PoisonError::into_inner) = Some(guard);
+}
 
 #[derive(Debug, Clone)]
 pub struct LoggingConfig {
     pub enable_console: bool,
     pub enable_json: bool,
+    pub enable_logfmt: bool,
     pub enable_debug: bool,
     pub verbose_mode: bool,
     pub include_timestamps: bool,
     pub enable_async_writer: bool,
+    pub async_writer: AsyncWriterConfig,
+    pub filter: Option<String>,
+    pub file: Option<FileLogConfig>,
+    pub session: Option<SessionConfig>,
+    pub syslog: Option<SyslogConfig>,
+    pub gelf: Option<GelfConfig>,
+    pub chrome_trace: Option<String>,
+    pub dedup: Option<DedupConfig>,
+    pub metrics: Option<MetricsConfig>,
+    pub redaction: Option<RedactionConfig>,
+    pub otlp: Option<OtlpConfig>,
+    pub history: Option<HistoryConfig>,
+    pub memory_buffer: Option<u32>,
+    pub console_output: LogOutput,
+    pub json_output: LogOutput,
+    pub logfmt_output: LogOutput,
+    pub logfmt_spans: bool,
+    pub json: JsonLogConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanEvent {
+    New,
+    Enter,
+    Exit,
+    Close,
+}
+
+impl SpanEvent {
+    fn fmt_span(self) -> FmtSpan {
+        match self {
+            SpanEvent::New => FmtSpan::NEW,
+            SpanEvent::Enter => FmtSpan::ENTER,
+            SpanEvent::Exit => FmtSpan::EXIT,
+            SpanEvent::Close => FmtSpan::CLOSE,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonLogConfig {
+    pub flatten_fields: bool,
+    pub include_target: bool,
+    pub include_thread_ids: bool,
+    pub include_current_span: bool,
+    pub include_span_list: bool,
+    pub span_events: Vec<SpanEvent>,
+    pub rename_keys: HashMap<String, String>,
+    pub static_fields: HashMap<String, String>,
+}
+
+impl From<crate::config::JsonLogConfig> for JsonLogConfig {
+    fn from(config: crate::config::JsonLogConfig) -> Self {
+        let span_events = [
+            SpanEvent::New,
+            SpanEvent::Enter,
+            SpanEvent::Exit,
+            SpanEvent::Close,
+        ]
+        .into_iter()
+        .filter(|event| config.span_events.clone() & event.fmt_span() == event.fmt_span())
+        .collect();
+
+        Self {
+            flatten_fields: config.flatten_fields,
+            include_target: config.include_target,
+            include_thread_ids: config.include_thread_ids,
+            include_current_span: config.include_current_span,
+            include_span_list: config.include_span_list,
+            span_events,
+            rename_keys: config.rename_keys.into_iter().collect(),
+            static_fields: config.static_fields.into_iter().collect(),
+        }
+    }
+}
+
+impl From<JsonLogConfig> for crate::config::JsonLogConfig {
+    fn from(config: JsonLogConfig) -> Self {
+        Self {
+            flatten_fields: config.flatten_fields,
+            include_target: config.include_target,
+            include_thread_ids: config.include_thread_ids,
+            include_current_span: config.include_current_span,
+            include_span_list: config.include_span_list,
+            span_events: config
+                .span_events
+                .into_iter()
+                .fold(FmtSpan::NONE, |events, event| events | event.fmt_span()),
+            rename_keys: config.rename_keys.into_iter().collect(),
+            static_fields: config.static_fields.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LogOutput {
+    Stdout,
+    Stderr,
+    Split,
+    File { path: String },
+}
+
+// Custom writers cannot cross the FFI boundary and never come out of a loaded config.
+impl From<crate::config::LogOutput> for LogOutput {
+    fn from(output: crate::config::LogOutput) -> Self {
+        match output {
+            crate::config::LogOutput::Stdout | crate::config::LogOutput::Custom(_) => Self::Stdout,
+            crate::config::LogOutput::Stderr => Self::Stderr,
+            crate::config::LogOutput::Split => Self::Split,
+            crate::config::LogOutput::File(path) => Self::File {
+                path: path.to_string_lossy().into_owned(),
+            },
+        }
+    }
+}
+
+impl From<LogOutput> for crate::config::LogOutput {
+    fn from(output: LogOutput) -> Self {
+        match output {
+            LogOutput::Stdout => Self::Stdout,
+            LogOutput::Stderr => Self::Stderr,
+            LogOutput::Split => Self::Split,
+            LogOutput::File { path } => Self::File(PathBuf::from(path)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileLogConfig {
+    pub directory: Option<String>,
+    pub file_name_prefix: String,
+    pub rotation: LogRotation,
+    pub max_files: u32,
+}
+
+impl From<crate::config::FileLogConfig> for FileLogConfig {
+    fn from(config: crate::config::FileLogConfig) -> Self {
+        Self {
+            directory: config.directory.map(|p| p.to_string_lossy().into_owned()),
+            file_name_prefix: config.file_name_prefix,
+            rotation: config.rotation,
+            max_files: config.max_files as u32,
+        }
+    }
+}
+
+impl From<FileLogConfig> for crate::config::FileLogConfig {
+    fn from(config: FileLogConfig) -> Self {
+        Self {
+            directory: config.directory.map(PathBuf::from),
+            file_name_prefix: config.file_name_prefix,
+            rotation: config.rotation,
+            max_files: config.max_files as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub directory: Option<String>,
+    pub file_name_prefix: String,
+    pub max_sessions: u32,
+    pub max_age_days: Option<u64>,
+}
+
+impl From<crate::config::SessionConfig> for SessionConfig {
+    fn from(config: crate::config::SessionConfig) -> Self {
+        Self {
+            directory: config.directory.map(|p| p.to_string_lossy().into_owned()),
+            file_name_prefix: config.file_name_prefix,
+            max_sessions: config.max_sessions as u32,
+            max_age_days: config.max_age_days,
+        }
+    }
+}
+
+impl From<SessionConfig> for crate::config::SessionConfig {
+    fn from(config: SessionConfig) -> Self {
+        Self {
+            directory: config.directory.map(PathBuf::from),
+            file_name_prefix: config.file_name_prefix,
+            max_sessions: config.max_sessions as usize,
+            max_age_days: config.max_age_days,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NetworkTransport {
+    Udp { address: String },
+    Tcp { address: String },
+    Unix { path: String },
+}
+
+impl From<crate::config::NetworkTransport> for NetworkTransport {
+    fn from(transport: crate::config::NetworkTransport) -> Self {
+        match transport {
+            crate::config::NetworkTransport::Udp(address) => Self::Udp { address },
+            crate::config::NetworkTransport::Tcp(address) => Self::Tcp { address },
+            crate::config::NetworkTransport::Unix(path) => Self::Unix {
+                path: path.to_string_lossy().into_owned(),
+            },
+        }
+    }
+}
+
+impl From<NetworkTransport> for crate::config::NetworkTransport {
+    fn from(transport: NetworkTransport) -> Self {
+        match transport {
+            NetworkTransport::Udp { address } => Self::Udp(address),
+            NetworkTransport::Tcp { address } => Self::Tcp(address),
+            NetworkTransport::Unix { path } => Self::Unix(PathBuf::from(path)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyslogConfig {
+    pub transport: NetworkTransport,
+    pub facility: u8,
+    pub hostname: Option<String>,
+    pub app_name: Option<String>,
+    pub min_level: LogLevel,
+}
+
+impl From<crate::config::SyslogConfig> for SyslogConfig {
+    fn from(config: crate::config::SyslogConfig) -> Self {
+        Self {
+            transport: config.transport.into(),
+            facility: config.facility,
+            hostname: config.hostname,
+            app_name: config.app_name,
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+impl From<SyslogConfig> for crate::config::SyslogConfig {
+    fn from(config: SyslogConfig) -> Self {
+        Self {
+            transport: config.transport.into(),
+            facility: config.facility,
+            hostname: config.hostname,
+            app_name: config.app_name,
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GelfConfig {
+    pub transport: NetworkTransport,
+    pub host: Option<String>,
+    pub static_fields: HashMap<String, String>,
+    pub min_level: LogLevel,
+}
+
+impl From<crate::config::GelfConfig> for GelfConfig {
+    fn from(config: crate::config::GelfConfig) -> Self {
+        Self {
+            transport: config.transport.into(),
+            host: config.host,
+            static_fields: config.static_fields.into_iter().collect(),
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+impl From<GelfConfig> for crate::config::GelfConfig {
+    fn from(config: GelfConfig) -> Self {
+        Self {
+            transport: config.transport.into(),
+            host: config.host,
+            static_fields: config.static_fields.into_iter().collect(),
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+    pub window_ms: u64,
+    pub max_repeats: u32,
+    pub target_limits: HashMap<String, u32>,
+}
+
+impl From<crate::config::DedupConfig> for DedupConfig {
+    fn from(config: crate::config::DedupConfig) -> Self {
+        Self {
+            window_ms: config.window.as_millis() as u64,
+            max_repeats: config.max_repeats,
+            target_limits: config.target_limits.into_iter().collect(),
+        }
+    }
+}
+
+impl From<DedupConfig> for crate::config::DedupConfig {
+    fn from(config: DedupConfig) -> Self {
+        Self {
+            window: Duration::from_millis(config.window_ms),
+            max_repeats: config.max_repeats,
+            target_limits: config.target_limits.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AsyncWriterConfig {
+    pub buffered_lines_limit: u32,
+    pub lossy: bool,
+}
+
+impl From<crate::config::AsyncWriterConfig> for AsyncWriterConfig {
+    fn from(config: crate::config::AsyncWriterConfig) -> Self {
+        Self {
+            buffered_lines_limit: config.buffered_lines_limit as u32,
+            lossy: config.lossy,
+        }
+    }
+}
+
+impl From<AsyncWriterConfig> for crate::config::AsyncWriterConfig {
+    fn from(config: AsyncWriterConfig) -> Self {
+        Self {
+            buffered_lines_limit: config.buffered_lines_limit as usize,
+            lossy: config.lossy,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub textfile: Option<String>,
+    pub port: Option<u16>,
+    pub interval_ms: u64,
+    pub namespace: Option<String>,
+}
+
+impl From<crate::config::MetricsConfig> for MetricsConfig {
+    fn from(config: crate::config::MetricsConfig) -> Self {
+        Self {
+            textfile: config.textfile.map(|p| p.to_string_lossy().into_owned()),
+            port: config.port,
+            interval_ms: config.interval.as_millis() as u64,
+            namespace: config.namespace,
+        }
+    }
+}
+
+impl From<MetricsConfig> for crate::config::MetricsConfig {
+    fn from(config: MetricsConfig) -> Self {
+        Self {
+            textfile: config.textfile.map(PathBuf::from),
+            port: config.port,
+            interval: Duration::from_millis(config.interval_ms),
+            namespace: config.namespace,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    pub protocol: OtlpProtocol,
+    pub endpoint: Option<String>,
+    pub service_name: Option<String>,
+    pub max_queue_size: u32,
+    pub max_export_batch_size: u32,
+    pub scheduled_delay_ms: u64,
+    pub timeout_ms: u64,
+    pub min_level: LogLevel,
+}
+
+impl From<crate::config::OtlpConfig> for OtlpConfig {
+    fn from(config: crate::config::OtlpConfig) -> Self {
+        Self {
+            protocol: config.protocol,
+            endpoint: config.endpoint,
+            service_name: config.service_name,
+            max_queue_size: config.max_queue_size as u32,
+            max_export_batch_size: config.max_export_batch_size as u32,
+            scheduled_delay_ms: config.scheduled_delay.as_millis() as u64,
+            timeout_ms: config.timeout.as_millis() as u64,
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+impl From<OtlpConfig> for crate::config::OtlpConfig {
+    fn from(config: OtlpConfig) -> Self {
+        Self {
+            protocol: config.protocol,
+            endpoint: config.endpoint,
+            service_name: config.service_name,
+            max_queue_size: config.max_queue_size as usize,
+            max_export_batch_size: config.max_export_batch_size as usize,
+            scheduled_delay: Duration::from_millis(config.scheduled_delay_ms),
+            timeout: Duration::from_millis(config.timeout_ms),
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    pub path: Option<String>,
+    pub batch_size: u32,
+    pub flush_interval_ms: u64,
+    pub max_buffered_events: u32,
+    pub retention_days: Option<u64>,
+    pub min_level: LogLevel,
+}
+
+impl From<crate::config::HistoryConfig> for HistoryConfig {
+    fn from(config: crate::config::HistoryConfig) -> Self {
+        Self {
+            path: config.path.map(|p| p.to_string_lossy().into_owned()),
+            batch_size: config.batch_size as u32,
+            flush_interval_ms: config.flush_interval.as_millis() as u64,
+            max_buffered_events: config.max_buffered_events as u32,
+            retention_days: config
+                .retention
+                .map(|retention| retention.as_secs() / (24 * 60 * 60)),
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+impl From<HistoryConfig> for crate::config::HistoryConfig {
+    fn from(config: HistoryConfig) -> Self {
+        Self {
+            path: config.path.map(PathBuf::from),
+            batch_size: config.batch_size as usize,
+            flush_interval: Duration::from_millis(config.flush_interval_ms),
+            max_buffered_events: config.max_buffered_events as usize,
+            retention: config
+                .retention_days
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            min_level: config.min_level.into(),
+        }
+    }
+}
+
+impl From<crate::config::LoggingConfig> for LoggingConfig {
+    fn from(config: crate::config::LoggingConfig) -> Self {
+        Self {
+            enable_console: config.enable_console,
+            enable_json: config.enable_json,
+            enable_logfmt: config.enable_logfmt,
+            enable_debug: config.enable_debug,
+            verbose_mode: config.verbose_mode,
+            include_timestamps: config.include_timestamps,
+            enable_async_writer: config.enable_async_writer,
+            async_writer: config.async_writer.into(),
+            filter: config.filter,
+            file: config.file.map(Into::into),
+            session: config.session.map(Into::into),
+            syslog: config.syslog.map(Into::into),
+            gelf: config.gelf.map(Into::into),
+            chrome_trace: config
+                .chrome_trace
+                .map(|p| p.to_string_lossy().into_owned()),
+            dedup: config.dedup.map(Into::into),
+            metrics: config.metrics.map(Into::into),
+            redaction: config.redaction,
+            otlp: config.otlp.map(Into::into),
+            history: config.history.map(Into::into),
+            memory_buffer: config.memory_buffer.map(|n| n as u32),
+            console_output: config.console_output.into(),
+            json_output: config.json_output.into(),
+            logfmt_output: config.logfmt_output.into(),
+            logfmt_spans: config.logfmt_spans,
+            json: config.json.into(),
+        }
+    }
 }
 
 impl From<LoggingConfig> for crate::config::LoggingConfig {
@@ -37,10 +524,143 @@ impl From<LoggingConfig> for crate::config::LoggingConfig {
         Self {
             enable_console: config.enable_console,
             enable_json: config.enable_json,
+            enable_logfmt: config.enable_logfmt,
             enable_debug: config.enable_debug,
             verbose_mode: config.verbose_mode,
             include_timestamps: config.include_timestamps,
             enable_async_writer: config.enable_async_writer,
+            async_writer: config.async_writer.into(),
+            filter: config.filter,
+            file: config.file.map(Into::into),
+            session: config.session.map(Into::into),
+            syslog: config.syslog.map(Into::into),
+            gelf: config.gelf.map(Into::into),
+            chrome_trace: config.chrome_trace.map(PathBuf::from),
+            dedup: config.dedup.map(Into::into),
+            metrics: config.metrics.map(Into::into),
+            redaction: config.redaction,
+            otlp: config.otlp.map(Into::into),
+            history: config.history.map(Into::into),
+            memory_buffer: config.memory_buffer.map(|n| n as usize),
+            console_output: config.console_output.into(),
+            json_output: config.json_output.into(),
+            logfmt_output: config.logfmt_output.into(),
+            logfmt_spans: config.logfmt_spans,
+            json: config.json.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadedLoggingConfig {
+    pub config: LoggingConfig,
+    pub config_file: Option<String>,
+    pub sources: HashMap<String, ConfigSource>,
+}
+
+impl From<crate::config_loader::LoadedLoggingConfig> for LoadedLoggingConfig {
+    fn from(loaded: crate::config_loader::LoadedLoggingConfig) -> Self {
+        Self {
+            config: loaded.config.into(),
+            config_file: loaded.config_file.map(|p| p.to_string_lossy().into_owned()),
+            sources: loaded
+                .sources
+                .into_iter()
+                .map(|(key, source)| (key.to_string(), source))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Self::ERROR,
+            LogLevel::Warn => Self::WARN,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Debug => Self::DEBUG,
+            LogLevel::Trace => Self::TRACE,
+        }
+    }
+}
+
+impl From<tracing::Level> for LogLevel {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::ERROR => Self::Error,
+            tracing::Level::WARN => Self::Warn,
+            tracing::Level::INFO => Self::Info,
+            tracing::Level::DEBUG => Self::Debug,
+            tracing::Level::TRACE => Self::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub id: u64,
+    pub timestamp: SystemTime,
+    pub level: LogLevel,
+    pub target: String,
+    pub message: String,
+    pub fields: HashMap<String, String>,
+}
+
+impl From<crate::memory::LogRecord> for LogRecord {
+    fn from(record: crate::memory::LogRecord) -> Self {
+        Self {
+            id: record.id,
+            timestamp: record.timestamp.into(),
+            level: record.level.into(),
+            target: record.target,
+            message: record.message,
+            fields: record.fields.into_iter().collect(),
+        }
+    }
+}
+
+pub trait LogSink: Send + Sync {
+    fn on_log(&self, record: LogRecord);
+}
+
+struct ForeignLogSink(Box<dyn LogSink>);
+
+impl crate::sink::LogSink for ForeignLogSink {
+    fn on_log(&self, record: crate::memory::LogRecord) {
+        self.0.on_log(record.into());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogQuery {
+    pub min_level: Option<LogLevel>,
+    pub target: Option<String>,
+    pub since: Option<SystemTime>,
+    pub until: Option<SystemTime>,
+    pub contains: Option<String>,
+    pub after_id: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+impl From<LogQuery> for crate::memory::LogQuery {
+    fn from(query: LogQuery) -> Self {
+        Self {
+            min_level: query.min_level.map(Into::into),
+            target: query.target,
+            since: query.since.map(Into::into),
+            until: query.until.map(Into::into),
+            contains: query.contains,
+            after_id: query.after_id,
+            limit: query.limit.map(|n| n as usize),
         }
     }
 }
@@ -63,21 +683,133 @@ impl From<crate::config::FeatureConfig> for FeatureConfig {
 }
 
 pub fn init_logging(config: LoggingConfig) -> Result<(), ConfigError> {
-    let guard = crate::config::init_logging(config.into())?;
-    if let Some(g) = guard {
-        let _ = LOGGER_GUARD.set(g);
-    }
+    store_guard(crate::config::init_logging(config.into())?);
     Ok(())
 }
 
 pub fn init_logging_default() -> Result<(), ConfigError> {
-    let guard = crate::config::init_logging_default()?;
-    if let Some(g) = guard {
-        let _ = LOGGER_GUARD.set(g);
-    }
+    store_guard(crate::config::init_logging_default()?);
     Ok(())
 }
 
+pub fn load_logging_config() -> Result<LoadedLoggingConfig, ConfigError> {
+    crate::config::LoggingConfig::load().map(Into::into)
+}
+
+pub fn flush_logging() {
+    crate::config::flush_logging();
+}
+
+pub fn shutdown_logging() {
+    let guard = LOGGER_GUARD
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    drop(guard);
+    crate::config::shutdown_logging();
+}
+
+pub fn set_log_level(level: LogLevel) -> Result<(), ConfigError> {
+    crate::config::set_log_level(level.into())
+}
+
+pub fn set_log_filter(directives: &str) -> Result<(), ConfigError> {
+    crate::config::set_log_filter(directives)
+}
+
+pub fn session_id() -> String {
+    crate::session::session_id().to_string()
+}
+
+pub fn session_log_path() -> Option<String> {
+    crate::session::session_file().map(|p| p.to_string_lossy().into_owned())
+}
+
+thread_local! {
+    static CONTEXT_GUARDS: RefCell<Vec<ContextGuard>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Adds `fields` to the calling thread's log context until the matching
+/// [`pop_log_context`], so every `log_*` call from this thread carries them.
+pub fn push_log_context(fields: HashMap<String, String>) {
+    let fields: BTreeMap<_, _> = fields.into_iter().collect();
+    let context = fields
+        .into_iter()
+        .fold(LogContext::current(), |context, (key, value)| {
+            context.with(key, value)
+        });
+    let guard = context.enter();
+    CONTEXT_GUARDS.with(|guards| guards.borrow_mut().push(guard));
+}
+
+pub fn pop_log_context() {
+    let guard = CONTEXT_GUARDS.with(|guards| guards.borrow_mut().pop());
+    drop(guard);
+}
+
+pub fn current_log_context() -> HashMap<String, String> {
+    LogContext::current().fields().iter().cloned().collect()
+}
+
+pub fn log_counts() -> LogCounts {
+    crate::summary::log_counts()
+}
+
+pub fn dropped_log_lines() -> u64 {
+    crate::async_writer::dropped_lines()
+}
+
+pub fn reset_log_counts() {
+    crate::summary::reset();
+}
+
+pub fn log_summary() -> String {
+    crate::summary::render()
+}
+
+pub fn print_log_summary() {
+    crate::summary::print_summary();
+}
+
+pub fn increment_counter(name: &str, value: f64) {
+    crate::metrics::increment_counter(name, value);
+}
+
+pub fn set_gauge(name: &str, value: f64) {
+    crate::metrics::set_gauge(name, value);
+}
+
+pub fn observe_histogram(name: &str, value: f64) {
+    crate::metrics::observe_histogram(name, value);
+}
+
+pub fn render_metrics(namespace: Option<String>) -> String {
+    crate::metrics::render(namespace.as_deref())
+}
+
+pub fn query_logs(query: LogQuery) -> Vec<LogRecord> {
+    crate::memory::query_logs(&query.into())
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
+
+pub fn recent_log_lines(count: u32) -> Vec<String> {
+    crate::memory::recent_lines(count as usize)
+}
+
+pub fn register_log_sink(sink: Box<dyn LogSink>, min_level: LogLevel) -> u64 {
+    crate::sink::register_log_sink(Arc::new(ForeignLogSink(sink)), min_level.into())
+}
+
+pub fn unregister_log_sink(id: u64) -> bool {
+    crate::sink::unregister_log_sink(id)
+}
+
+pub fn clear_log_sinks() {
+    crate::sink::clear_log_sinks();
+}
+
 pub fn get_feature_config() -> FeatureConfig {
     crate::config::FeatureConfig::from_features().into()
 }
@@ -126,7 +858,7 @@ pub async fn clear_all(dir: &str) -> Result<(), FileError> {
 }
 
 pub fn log_error_from_string(error_message: &str) {
-    tracing::error!("{}", error_message);
+    crate::error!("{}", error_message);
 }
 
 pub fn log_recoverable_error_from_string(error_message: &str, recovery_action: &str) {
@@ -135,73 +867,73 @@ pub fn log_recoverable_error_from_string(error_message: &str, recovery_action: &
 }
 
 pub fn log_info(message: &str) {
-    tracing::info!(message);
+    crate::info!(message);
 }
 
 pub fn log_success(message: &str) {
-    tracing::info!(success = true, message);
+    crate::info!(success = true, message);
 }
 
 pub fn log_error(message: &str) {
-    tracing::error!(message);
+    crate::error!(message);
 }
 
 pub fn log_warn(message: &str) {
-    tracing::warn!(message);
+    crate::warn!(message);
 }
 
 pub fn log_debug(message: &str) {
-    tracing::debug!(message);
+    crate::debug!(message);
 }
 
 pub fn log_trace(message: &str) {
-    tracing::trace!(message);
+    crate::trace!(message);
 }
 
 pub fn log_info_with_field(message: &str, value: &str) {
-    tracing::info!(value, message);
+    crate::info!(value, message);
 }
 
 pub fn log_success_with_field(message: &str, value: &str) {
-    tracing::info!(success = true, value, message);
+    crate::info!(success = true, value, message);
 }
 
 pub fn log_error_with_field(message: &str, value: &str) {
-    tracing::error!(value, message);
+    crate::error!(value, message);
 }
 
 pub fn log_warn_with_field(message: &str, value: &str) {
-    tracing::warn!(value, message);
+    crate::warn!(value, message);
 }
 
 pub fn log_debug_with_field(message: &str, value: &str) {
-    tracing::debug!(value, message);
+    crate::debug!(value, message);
 }
 
 pub fn log_trace_with_field(message: &str, value: &str) {
-    tracing::trace!(value, message);
+    crate::trace!(value, message);
 }
 
 pub fn log_info_with_fields(message: &str, fields: HashMap<String, String>) {
-    tracing::info!(fields = ?fields, message);
+    crate::info!(fields = ?fields, message);
 }
 
 pub fn log_success_with_fields(message: &str, fields: HashMap<String, String>) {
-    tracing::info!(success = true, fields = ?fields, message);
+    crate::info!(success = true, fields = ?fields, message);
 }
 
 pub fn log_error_with_fields(message: &str, fields: HashMap<String, String>) {
-    tracing::error!(fields = ?fields, message);
+    crate::error!(fields = ?fields, message);
 }
 
 pub fn log_warn_with_fields(message: &str, fields: HashMap<String, String>) {
-    tracing::warn!(fields = ?fields, message);
+    crate::warn!(fields = ?fields, message);
 }
 
 pub fn log_debug_with_fields(message: &str, fields: HashMap<String, String>) {
-    tracing::debug!(fields = ?fields, message);
+    crate::debug!(fields = ?fields, message);
 }
 
 pub fn log_trace_with_fields(message: &str, fields: HashMap<String, String>) {
-    tracing::trace!(fields = ?fields, message);
+    crate::trace!(fields = ?fields, message);
 }
@@ -0,0 +1,258 @@
+use crate::formatter::FieldCollector;
+use crate::redact::Redactor;
+
+use serde_json::{Map, Value};
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Events beyond this are dropped so a long run cannot grow the trace without bound.
+const MAX_TRACE_EVENTS: usize = 1_000_000;
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Begin,
+    End,
+    Instant,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::Begin => "B",
+            Phase::End => "E",
+            Phase::Instant => "i",
+        }
+    }
+}
+
+struct TraceEvent {
+    name: String,
+    category: &'static str,
+    phase: Phase,
+    timestamp_us: f64,
+    thread_id: u64,
+    args: Vec<(&'static str, String)>,
+}
+
+struct SpanArgs(Vec<(&'static str, String)>);
+
+/// Records span enter/exit and events in memory and writes them as a Chrome
+/// trace-event file, viewable in Perfetto or `chrome://tracing`.
+pub(crate) struct ChromeTrace {
+    path: PathBuf,
+    recorder: Arc<Recorder>,
+    redactor: Option<Arc<Redactor>>,
+}
+
+struct Recorder {
+    start: Instant,
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+impl ChromeTrace {
+    pub(crate) fn new(path: PathBuf, redactor: Option<Arc<Redactor>>) -> Self {
+        Self {
+            path,
+            recorder: Arc::new(Recorder {
+                start: Instant::now(),
+                events: Mutex::new(Vec::new()),
+            }),
+            redactor,
+        }
+    }
+
+    pub(crate) fn layer(&self) -> ChromeTraceLayer {
+        ChromeTraceLayer {
+            recorder: self.recorder.clone(),
+            redactor: self.redactor.clone(),
+        }
+    }
+
+    pub(crate) fn write(&self) -> io::Result<()> {
+        let events = std::mem::take(
+            &mut *self
+                .recorder
+                .events
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        write_trace(&self.path, &events)
+    }
+}
+
+fn write_trace(path: &Path, events: &[TraceEvent]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let pid = std::process::id();
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"{\"traceEvents\":[")?;
+
+    for (index, event) in events.iter().enumerate() {
+        if index > 0 {
+            writer.write_all(b",")?;
+        }
+
+        let mut object = Map::new();
+        object.insert("name".to_string(), event.name.as_str().into());
+        object.insert("cat".to_string(), event.category.into());
+        object.insert("ph".to_string(), event.phase.as_str().into());
+        object.insert("ts".to_string(), event.timestamp_us.into());
+        object.insert("pid".to_string(), pid.into());
+        object.insert("tid".to_string(), event.thread_id.into());
+        if let Phase::Instant = event.phase {
+            object.insert("s".to_string(), "t".into());
+        }
+        if !event.args.is_empty() {
+            let args = event
+                .args
+                .iter()
+                .map(|(name, value)| (name.to_string(), Value::from(value.as_str())));
+            object.insert("args".to_string(), Value::Object(args.collect()));
+        }
+
+        serde_json::to_writer(&mut writer, &Value::Object(object))?;
+    }
+
+    writer.write_all(b"],\"displayTimeUnit\":\"ms\"}")?;
+    writer.flush()
+}
+
+pub(crate) struct ChromeTraceLayer {
+    recorder: Arc<Recorder>,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl ChromeTraceLayer {
+    fn push(
+        &self,
+        name: String,
+        category: &'static str,
+        phase: Phase,
+        args: Vec<(&'static str, String)>,
+    ) {
+        let event = TraceEvent {
+            name,
+            category,
+            phase,
+            timestamp_us: self.recorder.start.elapsed().as_secs_f64() * 1_000_000.0,
+            thread_id: thread_id(),
+            args,
+        };
+
+        let mut events = self
+            .recorder
+            .events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if events.len() < MAX_TRACE_EVENTS {
+            events.push(event);
+        }
+    }
+
+    /// Span args and event fields, messages included, go through the redactor.
+    fn collect_args(&self, visitor: FieldCollector) -> Vec<(&'static str, String)> {
+        visitor
+            .fields
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match &self.redactor {
+                    Some(redactor) => redactor.redact_field(name, value),
+                    None => value,
+                };
+                (name, value.into_owned())
+            })
+            .collect()
+    }
+}
+
+impl<S> Layer<S> for ChromeTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldCollector::new();
+        attrs.record(&mut visitor);
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut()
+                .insert(SpanArgs(self.collect_args(visitor)));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldCollector::new();
+        values.record(&mut visitor);
+
+        if let Some(span) = ctx.span(id)
+            && let Some(args) = span.extensions_mut().get_mut::<SpanArgs>()
+        {
+            args.0.extend(self.collect_args(visitor));
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let args = span
+                .extensions()
+                .get::<SpanArgs>()
+                .map(|args| args.0.clone())
+                .unwrap_or_default();
+            self.push(
+                span.name().to_string(),
+                span.metadata().target(),
+                Phase::Begin,
+                args,
+            );
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            self.push(
+                span.name().to_string(),
+                span.metadata().target(),
+                Phase::End,
+                Vec::new(),
+            );
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldCollector::new();
+        event.record(&mut visitor);
+
+        let mut args = self.collect_args(visitor);
+        let name = match args.iter().position(|(name, _)| *name == "message") {
+            Some(index) => args.remove(index).1,
+            None => event.metadata().name().to_string(),
+        };
+
+        self.push(name, event.metadata().target(), Phase::Instant, args);
+    }
+}
+
+fn thread_id() -> u64 {
+    THREAD_ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
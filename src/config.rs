@@ -1,23 +1,413 @@
-use crate::async_writer::AsyncMakeWriter;
+use crate::async_writer::{AsyncMakeWriter, AsyncWriterGuard, SharedMakeWriter};
+use crate::chrome::ChromeTrace;
+use crate::dedup::{Dedup, DedupFlusher, DedupLayer};
 use crate::error::ConfigError;
 use crate::formatter::ConsoleFormatter;
+use crate::gelf::GelfFormatter;
+#[cfg(feature = "sqlite")]
+use crate::history::HistoryWriter;
+use crate::json::JsonFormatter;
+use crate::logfmt::LogfmtFormatter;
+use crate::memory::MemoryLayer;
+use crate::metrics::{MetricsExporter, MetricsLayer};
+use crate::network::{NetworkWriter, Protocol};
+#[cfg(feature = "otlp")]
+use crate::otlp::OtlpExporter;
+use crate::redact::Redactor;
+use crate::rolling::open_log_writer;
+use crate::session::Session;
+use crate::sink::SinkLayer;
+use crate::summary::SummaryLayer;
+use crate::syslog::SyslogFormatter;
 
-use tracing_appender::non_blocking::WorkerGuard;
+use owo_colors::Stream;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::Duration;
+use tracing::Level;
 use tracing_subscriber::{
-    fmt::{self, format::FmtSpan},
-    layer::SubscriberExt,
+    EnvFilter, Layer,
+    filter::LevelFilter,
+    fmt::{
+        self, MakeWriter,
+        format::{FmtSpan, JsonFields},
+        writer::{BoxMakeWriter, MakeWriterExt},
+    },
+    layer::{Layered, SubscriberExt},
+    registry::Registry,
+    reload,
     util::SubscriberInitExt,
-    EnvFilter,
 };
 
 #[derive(Debug, Clone)]
 pub struct LoggingConfig {
     pub enable_console: bool,
     pub enable_json: bool,
+    pub enable_logfmt: bool,
     pub enable_debug: bool,
     pub verbose_mode: bool,
     pub include_timestamps: bool,
     pub enable_async_writer: bool,
+    pub async_writer: AsyncWriterConfig,
+    /// `EnvFilter` directives such as `baad=trace,hyper=warn`. `BAAD_LOG` or `RUST_LOG`
+    /// take precedence when set.
+    pub filter: Option<String>,
+    pub file: Option<FileLogConfig>,
+    /// Writes this run to its own file, framed by a header and footer banner.
+    pub session: Option<SessionConfig>,
+    pub syslog: Option<SyslogConfig>,
+    pub gelf: Option<GelfConfig>,
+    /// Records span timings and events and writes them to this path as a Chrome
+    /// trace-event file when logging shuts down.
+    pub chrome_trace: Option<PathBuf>,
+    /// Collapses repeated console messages into "repeated N times" summaries.
+    pub dedup: Option<DedupConfig>,
+    /// Feeds the metrics registry from `metric.*` event fields and exports it.
+    pub metrics: Option<MetricsConfig>,
+    /// Masks secrets in console, file and JSON output.
+    pub redaction: Option<RedactionConfig>,
+    /// Exports spans and events over OTLP.
+    pub otlp: Option<OtlpConfig>,
+    /// Keeps a queryable history of events in SQLite.
+    pub history: Option<HistoryConfig>,
+    /// Keeps this many recent events in memory for [`crate::memory::query_logs`].
+    pub memory_buffer: Option<usize>,
+    pub console_output: LogOutput,
+    pub json_output: LogOutput,
+    pub logfmt_output: LogOutput,
+    /// Adds the current span names to logfmt lines as `span=outer:inner`.
+    pub logfmt_spans: bool,
+    pub json: JsonLogConfig,
+}
+
+/// Buffering for the background writer threads used when `enable_async_writer` is
+/// set. When the buffer is full a lossy writer drops the line and counts it in
+/// [`crate::async_writer::dropped_lines`]; otherwise the logging thread waits.
+#[derive(Debug, Clone)]
+pub struct AsyncWriterConfig {
+    pub buffered_lines_limit: usize,
+    pub lossy: bool,
+}
+
+impl Default for AsyncWriterConfig {
+    fn default() -> Self {
+        Self {
+            buffered_lines_limit: 128_000,
+            lossy: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonLogConfig {
+    pub flatten_fields: bool,
+    pub include_target: bool,
+    pub include_thread_ids: bool,
+    pub include_current_span: bool,
+    pub include_span_list: bool,
+    pub span_events: FmtSpan,
+    /// Top-level keys to rename, e.g. `level` → `severity`.
+    pub rename_keys: BTreeMap<String, String>,
+    /// Added to every line, e.g. app name, version or session id.
+    pub static_fields: BTreeMap<String, String>,
+}
+
+impl Default for JsonLogConfig {
+    fn default() -> Self {
+        Self {
+            flatten_fields: false,
+            include_target: true,
+            include_thread_ids: true,
+            include_current_span: true,
+            include_span_list: true,
+            span_events: FmtSpan::CLOSE,
+            rename_keys: BTreeMap::new(),
+            static_fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// Where a logging layer writes its lines. `Custom` writers are used as-is and are
+/// never wrapped by the async writer.
+#[derive(Debug, Clone)]
+pub enum LogOutput {
+    Stdout,
+    Stderr,
+    /// WARN and ERROR go to stderr, everything else to stdout.
+    Split,
+    File(PathBuf),
+    Custom(SharedMakeWriter),
+}
+
+impl LogOutput {
+    pub fn custom<M>(make_writer: M) -> Self
+    where
+        M: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        Self::Custom(SharedMakeWriter::new(make_writer))
+    }
+
+    fn is_terminal(&self) -> bool {
+        matches!(self, Self::Stdout | Self::Stderr | Self::Split)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Daily,
+    Hourly,
+    Size { max_bytes: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct FileLogConfig {
+    pub directory: Option<PathBuf>,
+    pub file_name_prefix: String,
+    pub rotation: LogRotation,
+    /// Counts the current file, so it must be at least 1; size rotation needs at least 2.
+    pub max_files: usize,
+}
+
+impl Default for FileLogConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            file_name_prefix: "baad".to_string(),
+            rotation: LogRotation::Daily,
+            max_files: 7,
+        }
+    }
+}
+
+/// One log file per process run, named `<prefix>-<session id>.log`. Older session
+/// files are pruned once there are more than `max_sessions` or they are older than
+/// `max_age_days`.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub directory: Option<PathBuf>,
+    pub file_name_prefix: String,
+    pub max_sessions: usize,
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            file_name_prefix: "session".to_string(),
+            max_sessions: 10,
+            max_age_days: Some(14),
+        }
+    }
+}
+
+/// Where the syslog and GELF sinks send their messages. Addresses are `host:port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkTransport {
+    Udp(String),
+    Tcp(String),
+    /// A Unix datagram socket such as `/dev/log`. Syslog only.
+    Unix(PathBuf),
+}
+
+/// Ships events as RFC 5424 syslog messages. `hostname` and `app_name` default to
+/// the machine's hostname and the configured app name.
+#[derive(Debug, Clone)]
+pub struct SyslogConfig {
+    pub transport: NetworkTransport,
+    pub facility: u8,
+    pub hostname: Option<String>,
+    pub app_name: Option<String>,
+    pub min_level: Level,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            transport: NetworkTransport::Udp("127.0.0.1:514".to_string()),
+            facility: 1,
+            hostname: None,
+            app_name: None,
+            min_level: Level::INFO,
+        }
+    }
+}
+
+/// Ships events as GELF 1.1 messages, e.g. to a Graylog input.
+#[derive(Debug, Clone)]
+pub struct GelfConfig {
+    pub transport: NetworkTransport,
+    pub host: Option<String>,
+    /// Added to every message as `_<key>`.
+    pub static_fields: BTreeMap<String, String>,
+    pub min_level: Level,
+}
+
+impl Default for GelfConfig {
+    fn default() -> Self {
+        Self {
+            transport: NetworkTransport::Udp("127.0.0.1:12201".to_string()),
+            host: None,
+            static_fields: BTreeMap::new(),
+            min_level: Level::INFO,
+        }
+    }
+}
+
+/// Lets through at most `max_repeats` identical messages per `window`. Entries in
+/// `target_limits` override `max_repeats` for targets starting with the key; a limit
+/// of 0 turns deduplication off for that target.
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+    pub window: Duration,
+    pub max_repeats: u32,
+    pub target_limits: BTreeMap<String, u32>,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(10),
+            max_repeats: 1,
+            target_limits: BTreeMap::new(),
+        }
+    }
+}
+
+/// Exports [`crate::metrics`] in the Prometheus text format, to `textfile` every
+/// `interval` and/or over HTTP on `127.0.0.1:<port>`.
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub textfile: Option<PathBuf>,
+    pub port: Option<u16>,
+    pub interval: Duration,
+    /// Prefixed to every metric name, e.g. `baad_bytes_downloaded`.
+    pub namespace: Option<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            textfile: None,
+            port: None,
+            interval: Duration::from_secs(15),
+            namespace: None,
+        }
+    }
+}
+
+/// Values of `fields` are replaced with `[REDACTED]`, as are URL query parameter
+/// values when `query_params` is set. Each of `patterns` is a regex whose capture
+/// groups, or whole match if it has none, are masked in every value and message.
+#[derive(Debug, Clone)]
+pub struct RedactionConfig {
+    pub fields: Vec<String>,
+    pub query_params: bool,
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            fields: ["token", "password", "authorization", "cookie"]
+                .map(String::from)
+                .to_vec(),
+            query_params: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtlpProtocol {
+    HttpProtobuf,
+    Grpc,
+}
+
+/// Exports spans, and events as log records, to an OpenTelemetry collector. Needs the
+/// `otlp` feature. Exports are batched on background threads; once `max_queue_size`
+/// spans or records are waiting, new ones are dropped.
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    pub protocol: OtlpProtocol,
+    /// Defaults to `http://127.0.0.1:4318` for HTTP and `http://127.0.0.1:4317` for
+    /// gRPC. HTTP exports go to `/v1/traces` and `/v1/logs` under it.
+    pub endpoint: Option<String>,
+    /// Defaults to the application name.
+    pub service_name: Option<String>,
+    pub max_queue_size: usize,
+    pub max_export_batch_size: usize,
+    pub scheduled_delay: Duration,
+    pub timeout: Duration,
+    /// Events below this level are not exported as log records.
+    pub min_level: Level,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            protocol: OtlpProtocol::HttpProtobuf,
+            endpoint: None,
+            service_name: None,
+            max_queue_size: 2048,
+            max_export_batch_size: 512,
+            scheduled_delay: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            min_level: Level::INFO,
+        }
+    }
+}
+
+/// Stores events in a SQLite database that `crate::history::query_history` can
+/// search across runs. Needs the `sqlite` feature. Events are inserted in batches of
+/// up to `batch_size` at least every `flush_interval`; once `max_buffered_events`
+/// are waiting, new ones are dropped. Events older than `retention` are deleted.
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    /// Defaults to `logs.sqlite` in [`crate::file::data_dir`].
+    pub path: Option<PathBuf>,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    pub max_buffered_events: usize,
+    pub retention: Option<Duration>,
+    pub min_level: Level,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            batch_size: 256,
+            flush_interval: Duration::from_secs(1),
+            max_buffered_events: 10_000,
+            retention: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            min_level: Level::INFO,
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub(crate) fn redactor(&self) -> Result<Option<Arc<Redactor>>, ConfigError> {
+        match &self.redaction {
+            Some(redaction) => Ok(Some(Arc::new(Redactor::new(redaction)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// The level used where `filter` and the environment set none.
+    pub(crate) fn default_level(&self) -> LevelFilter {
+        let debug_enabled = FeatureConfig::from_features().debug_enabled;
+        match (self.verbose_mode, self.enable_debug && debug_enabled) {
+            (true, _) => LevelFilter::TRACE,
+            (false, true) => LevelFilter::DEBUG,
+            (false, false) => LevelFilter::INFO,
+        }
+    }
 }
 
 impl Default for LoggingConfig {
@@ -27,10 +417,29 @@ impl Default for LoggingConfig {
         Self {
             enable_console: feature_config.logs_enabled,
             enable_json: false,
+            enable_logfmt: false,
             enable_debug: feature_config.debug_enabled,
             verbose_mode: false,
             include_timestamps: true,
             enable_async_writer: true,
+            async_writer: AsyncWriterConfig::default(),
+            filter: None,
+            file: None,
+            session: None,
+            syslog: None,
+            gelf: None,
+            chrome_trace: None,
+            dedup: None,
+            metrics: None,
+            redaction: None,
+            otlp: None,
+            history: None,
+            memory_buffer: None,
+            console_output: LogOutput::Stdout,
+            json_output: LogOutput::Stdout,
+            logfmt_output: LogOutput::Stdout,
+            logfmt_spans: false,
+            json: JsonLogConfig::default(),
         }
     }
 }
@@ -52,9 +461,337 @@ impl FeatureConfig {
     }
 }
 
-pub fn init_logging(config: LoggingConfig) -> Result<Option<WorkerGuard>, ConfigError> {
+type FilterHandle = reload::Handle<EnvFilter, Registry>;
+type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+type BoxedLayer = Box<dyn Layer<FilteredRegistry> + Send + Sync>;
+type LayersHandle = reload::Handle<Vec<BoxedLayer>, FilteredRegistry>;
+
+const LOG_ENV_VARS: &[&str] = &["BAAD_LOG", "RUST_LOG"];
+
+struct LoggingHandles {
+    filter: FilterHandle,
+    layers: LayersHandle,
+}
+
+struct ActiveLogging {
+    generation: u64,
+    default_level: LevelFilter,
+    session: Option<Session>,
+    chrome_trace: Option<ChromeTrace>,
+    metrics: Option<MetricsExporter>,
+    dedup: Option<DedupFlusher>,
+    #[cfg(feature = "otlp")]
+    otlp: Option<OtlpExporter>,
+    #[cfg(feature = "sqlite")]
+    history: Option<HistoryWriter>,
+    writers: Vec<AsyncWriterGuard>,
+}
+
+static LOGGING_HANDLES: OnceLock<LoggingHandles> = OnceLock::new();
+static ACTIVE_LOGGING: Mutex<Option<ActiveLogging>> = Mutex::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Runs [`flush_logging`] when dropped, unless logging has since been shut down and
+/// initialized again. Logging keeps running after the guard is dropped; only
+/// [`shutdown_logging`] detaches the outputs and writes the session footer and the
+/// Chrome trace.
+#[must_use = "dropping the guard flushes pending output"]
+pub struct LoggingGuard {
+    generation: Option<u64>,
+}
+
+impl Drop for LoggingGuard {
+    fn drop(&mut self) {
+        if let Some(active) = lock_active().as_ref()
+            && Some(active.generation) == self.generation
+        {
+            active.flush();
+        }
+    }
+}
+
+impl ActiveLogging {
+    fn flush(&self) {
+        if let Some(dedup) = &self.dedup {
+            dedup.flush();
+        }
+
+        for writer in &self.writers {
+            writer.flush();
+        }
+
+        if let Some(metrics) = &self.metrics {
+            metrics.flush();
+        }
+
+        #[cfg(feature = "otlp")]
+        if let Some(otlp) = &self.otlp {
+            otlp.flush();
+        }
+
+        #[cfg(feature = "sqlite")]
+        if let Some(history) = &self.history {
+            history.flush();
+        }
+    }
+}
+
+struct WriterFactory {
+    async_config: Option<AsyncWriterConfig>,
+    stdout: Option<AsyncMakeWriter>,
+    stderr: Option<AsyncMakeWriter>,
+    guards: Vec<AsyncWriterGuard>,
+}
+
+impl WriterFactory {
+    fn new(async_config: Option<AsyncWriterConfig>) -> Self {
+        Self {
+            async_config,
+            stdout: None,
+            stderr: None,
+            guards: Vec::new(),
+        }
+    }
+
+    fn make(&mut self, output: &LogOutput) -> Result<BoxMakeWriter, ConfigError> {
+        if self.async_config.is_none() {
+            return Ok(match output {
+                LogOutput::Stdout => BoxMakeWriter::new(io::stdout),
+                LogOutput::Stderr => BoxMakeWriter::new(io::stderr),
+                LogOutput::Split => {
+                    BoxMakeWriter::new(io::stderr.with_max_level(Level::WARN).or_else(io::stdout))
+                }
+                LogOutput::File(path) => BoxMakeWriter::new(Mutex::new(open_append(path)?)),
+                LogOutput::Custom(writer) => BoxMakeWriter::new(writer.clone()),
+            });
+        }
+
+        Ok(match output {
+            LogOutput::Stdout => BoxMakeWriter::new(self.async_stdout()),
+            LogOutput::Stderr => BoxMakeWriter::new(self.async_stderr()),
+            LogOutput::Split => BoxMakeWriter::new(
+                self.async_stderr()
+                    .with_max_level(Level::WARN)
+                    .or_else(self.async_stdout()),
+            ),
+            LogOutput::File(path) => BoxMakeWriter::new(self.spawn(open_append(path)?)),
+            LogOutput::Custom(writer) => BoxMakeWriter::new(writer.clone()),
+        })
+    }
+
+    fn async_stdout(&mut self) -> AsyncMakeWriter {
+        if let Some(writer) = &self.stdout {
+            return writer.clone();
+        }
+
+        let writer = self.spawn(io::stdout());
+        self.stdout = Some(writer.clone());
+        writer
+    }
+
+    fn async_stderr(&mut self) -> AsyncMakeWriter {
+        if let Some(writer) = &self.stderr {
+            return writer.clone();
+        }
+
+        let writer = self.spawn(io::stderr());
+        self.stderr = Some(writer.clone());
+        writer
+    }
+
+    fn make_from<W>(&mut self, writer: W) -> BoxMakeWriter
+    where
+        W: io::Write + Send + 'static,
+    {
+        if self.async_config.is_some() {
+            BoxMakeWriter::new(self.spawn(writer))
+        } else {
+            BoxMakeWriter::new(Mutex::new(writer))
+        }
+    }
+
+    fn spawn<W>(&mut self, writer: W) -> AsyncMakeWriter
+    where
+        W: io::Write + Send + 'static,
+    {
+        let config = self.async_config.clone().unwrap_or_default();
+        let (async_writer, guard) = AsyncMakeWriter::with_config(writer, &config);
+        self.guards.push(guard);
+        async_writer
+    }
+}
+
+fn open_append(path: &Path) -> Result<fs::File, ConfigError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| ConfigError::External(Box::new(e)))?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| ConfigError::External(Box::new(e)))
+}
+
+fn console_layer(writer: BoxMakeWriter, ansi: bool, formatter: ConsoleFormatter) -> BoxedLayer {
+    fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi)
+        .event_format(formatter)
+        .boxed()
+}
+
+pub(crate) fn console_formatter(
+    config: &LoggingConfig,
+    redactor: Option<&Arc<Redactor>>,
+) -> ConsoleFormatter {
+    let output = &config.console_output;
+    let mut formatter = ConsoleFormatter::new()
+        .with_timestamps(config.include_timestamps)
+        .with_stream(match output {
+            LogOutput::Stderr => Stream::Stderr,
+            _ => Stream::Stdout,
+        })
+        .with_split_streams(matches!(output, LogOutput::Split));
+    if let Some(redactor) = redactor {
+        formatter = formatter.with_redactor(redactor.clone());
+    }
+    formatter
+}
+
+fn json_layer(
+    writer: BoxMakeWriter,
+    config: &JsonLogConfig,
+    redactor: Option<&Arc<Redactor>>,
+) -> BoxedLayer {
+    fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_span_events(config.span_events.clone())
+        .fmt_fields(JsonFields::new())
+        .event_format(json_formatter(config, redactor))
+        .boxed()
+}
+
+pub(crate) fn json_formatter(
+    config: &JsonLogConfig,
+    redactor: Option<&Arc<Redactor>>,
+) -> JsonFormatter {
+    let mut formatter = JsonFormatter::new()
+        .with_flatten_fields(config.flatten_fields)
+        .with_target(config.include_target)
+        .with_thread_ids(config.include_thread_ids)
+        .with_current_span(config.include_current_span)
+        .with_span_list(config.include_span_list);
+
+    for (key, name) in &config.rename_keys {
+        formatter = formatter.with_key_name(key, name);
+    }
+    for (key, value) in &config.static_fields {
+        formatter = formatter.with_static_field(key, value.as_str());
+    }
+    if let Some(redactor) = redactor {
+        formatter = formatter.with_redactor(redactor.clone());
+    }
+    formatter
+}
+
+fn logfmt_layer(
+    writer: BoxMakeWriter,
+    include_spans: bool,
+    redactor: Option<&Arc<Redactor>>,
+) -> BoxedLayer {
+    let mut formatter = LogfmtFormatter::new().with_spans(include_spans);
+    if let Some(redactor) = redactor {
+        formatter = formatter.with_redactor(redactor.clone());
+    }
+
+    fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false)
+        .event_format(formatter)
+        .boxed()
+}
+
+fn file_layer(writer: BoxMakeWriter, redactor: Option<&Arc<Redactor>>) -> BoxedLayer {
+    let mut formatter = ConsoleFormatter::new()
+        .with_timestamps(true)
+        .with_dates(true);
+    if let Some(redactor) = redactor {
+        formatter = formatter.with_redactor(redactor.clone());
+    }
+
+    fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false)
+        .event_format(formatter)
+        .boxed()
+}
+
+fn syslog_layer(
+    writer: BoxMakeWriter,
+    config: &SyslogConfig,
+    redactor: Option<&Arc<Redactor>>,
+) -> BoxedLayer {
+    let mut formatter = SyslogFormatter::new().with_facility(config.facility);
+    if let Some(hostname) = &config.hostname {
+        formatter = formatter.with_hostname(hostname);
+    }
+    if let Some(app_name) = &config.app_name {
+        formatter = formatter.with_app_name(app_name);
+    }
+    if let Some(redactor) = redactor {
+        formatter = formatter.with_redactor(redactor.clone());
+    }
+
+    fmt::layer()
+        .with_writer(writer.with_max_level(config.min_level))
+        .with_ansi(false)
+        .event_format(formatter)
+        .boxed()
+}
+
+fn gelf_layer(
+    writer: BoxMakeWriter,
+    config: &GelfConfig,
+    redactor: Option<&Arc<Redactor>>,
+) -> Result<BoxedLayer, ConfigError> {
+    if let NetworkTransport::Unix(path) = &config.transport {
+        return Err(ConfigError::InvalidValue {
+            key: "gelf.transport".to_string(),
+            value: path.display().to_string(),
+            reason: "GELF supports udp and tcp only".to_string(),
+        });
+    }
+
+    let mut formatter = GelfFormatter::new();
+    if let Some(host) = &config.host {
+        formatter = formatter.with_host(host);
+    }
+    for (key, value) in &config.static_fields {
+        formatter = formatter.with_static_field(key, value.as_str());
+    }
+    if let Some(redactor) = redactor {
+        formatter = formatter.with_redactor(redactor.clone());
+    }
+
+    Ok(fmt::layer()
+        .with_writer(writer.with_max_level(config.min_level))
+        .with_ansi(false)
+        .event_format(formatter)
+        .boxed())
+}
+
+pub fn init_logging(config: LoggingConfig) -> Result<LoggingGuard, ConfigError> {
     let feature_config = FeatureConfig::from_features();
 
+    let default_level = config.default_level();
+
+    let env_filter = match env_directives().or_else(|| config.filter.clone()) {
+        Some(directives) => parse_filter(&directives, default_level)?,
+        None => EnvFilter::new(default_level.to_string()),
+    };
+
     if feature_config.logs_enabled
         && feature_config.error_enabled
         && let Err(e) = crate::error::install()
@@ -63,93 +800,315 @@ pub fn init_logging(config: LoggingConfig) -> Result<Option<WorkerGuard>, Config
     }
 
     if !feature_config.logs_enabled {
-        tracing_subscriber::registry().init();
-        return Ok(None);
+        tracing_subscriber::registry()
+            .try_init()
+            .map_err(|_| ConfigError::LoggingInitFailed)?;
+        return Ok(LoggingGuard { generation: None });
+    }
+
+    let redactor = config.redactor()?;
+
+    #[cfg(not(feature = "otlp"))]
+    if config.otlp.is_some() {
+        return Err(ConfigError::InvalidValue {
+            key: "otlp".to_string(),
+            value: "enabled".to_string(),
+            reason: "baad_core was built without the `otlp` feature".to_string(),
+        });
     }
 
-    let env_filter = match (
-        config.verbose_mode,
-        config.enable_debug && feature_config.debug_enabled,
-    ) {
-        (true, _) => EnvFilter::new("trace"),
-        (false, true) => EnvFilter::new("debug"),
-        (false, false) => EnvFilter::new("info"),
+    #[cfg(not(feature = "sqlite"))]
+    if config.history.is_some() {
+        return Err(ConfigError::InvalidValue {
+            key: "history".to_string(),
+            value: "enabled".to_string(),
+            reason: "baad_core was built without the `sqlite` feature".to_string(),
+        });
+    }
+
+    let mut active = lock_active();
+    if active.is_some() {
+        return Err(ConfigError::LoggingInitFailed);
+    }
+
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut writers = WriterFactory::new(
+        config
+            .enable_async_writer
+            .then(|| config.async_writer.clone()),
+    );
+
+    let mut dedup = None;
+    if config.enable_console {
+        let writer = writers.make(&config.console_output)?;
+        let ansi = config.console_output.is_terminal();
+        let formatter = console_formatter(&config, redactor.as_ref());
+        match &config.dedup {
+            Some(dedup_config) => {
+                let writer = SharedMakeWriter::new(writer);
+                let shared = Arc::new(Dedup::new(
+                    dedup_config.clone(),
+                    writer.clone(),
+                    formatter.clone(),
+                    ansi,
+                ));
+                let layer = console_layer(BoxMakeWriter::new(writer), ansi, formatter);
+                layers.push(Box::new(DedupLayer::new(layer, shared.clone())));
+                if !dedup_config.window.is_zero() {
+                    dedup = Some(DedupFlusher::start(shared)?);
+                }
+            }
+            None => layers.push(console_layer(writer, ansi, formatter)),
+        }
+    }
+
+    if config.enable_json {
+        let writer = writers.make(&config.json_output)?;
+        layers.push(json_layer(writer, &config.json, redactor.as_ref()));
+    }
+
+    if config.enable_logfmt {
+        let writer = writers.make(&config.logfmt_output)?;
+        layers.push(logfmt_layer(writer, config.logfmt_spans, redactor.as_ref()));
+    }
+
+    if let Some(file_config) = &config.file {
+        let writer = writers.make_from(open_log_writer(file_config)?);
+        layers.push(file_layer(writer, redactor.as_ref()));
+    }
+
+    let session = match &config.session {
+        Some(session_config) => {
+            let path = crate::session::prepare(session_config)?;
+            let writer = SharedMakeWriter::new(writers.make(&LogOutput::File(path.clone()))?);
+            let session = Session::start(path, writer.clone());
+            layers.push(file_layer(BoxMakeWriter::new(writer), redactor.as_ref()));
+            Some(session)
+        }
+        None => None,
+    };
+
+    if let Some(syslog_config) = &config.syslog {
+        let network = NetworkWriter::new(syslog_config.transport.clone(), Protocol::Syslog);
+        layers.push(syslog_layer(
+            writers.make_from(network),
+            syslog_config,
+            redactor.as_ref(),
+        ));
+    }
+
+    if let Some(gelf_config) = &config.gelf {
+        let network = NetworkWriter::new(gelf_config.transport.clone(), Protocol::Gelf);
+        layers.push(gelf_layer(
+            writers.make_from(network),
+            gelf_config,
+            redactor.as_ref(),
+        )?);
+    }
+
+    let chrome_trace = config
+        .chrome_trace
+        .clone()
+        .map(|path| ChromeTrace::new(path, redactor.clone()));
+    if let Some(chrome_trace) = &chrome_trace {
+        layers.push(Box::new(chrome_trace.layer()));
+    }
+
+    let metrics = match &config.metrics {
+        Some(metrics_config) => {
+            layers.push(Box::new(MetricsLayer));
+            Some(MetricsExporter::start(metrics_config)?)
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "otlp")]
+    let otlp = match &config.otlp {
+        Some(otlp_config) => {
+            let exporter = OtlpExporter::start(otlp_config, redactor.as_ref())?;
+            layers.push(Box::new(exporter.log_layer(redactor.as_ref())));
+            Some(exporter)
+        }
+        None => None,
     };
 
-    let guard = if config.enable_async_writer {
-        let (async_writer, guard) = AsyncMakeWriter::new();
-
-        macro_rules! console_layer {
-            () => {
-                fmt::layer().with_writer(async_writer.clone()).event_format(
-                    ConsoleFormatter::new().with_timestamps(config.include_timestamps),
-                )
-            };
-        }
-
-        macro_rules! json_layer {
-            () => {
-                fmt::layer()
-                    .with_writer(async_writer.clone())
-                    .json()
-                    .with_target(true)
-                    .with_thread_ids(true)
-                    .with_span_events(FmtSpan::CLOSE)
-            };
-        }
-
-        let subscriber = tracing_subscriber::registry().with(env_filter);
-
-        let result = match (config.enable_console, config.enable_json) {
-            (true, true) => subscriber
-                .with(console_layer!())
-                .with(json_layer!())
-                .try_init(),
-            (true, false) => subscriber.with(console_layer!()).try_init(),
-            (false, true) => subscriber.with(json_layer!()).try_init(),
-            (false, false) => subscriber.try_init(),
-        };
-
-        result.map_err(|_| ConfigError::LoggingInitFailed)?;
-        Some(guard)
-    } else {
-        macro_rules! console_layer {
-            () => {
-                fmt::layer().event_format(
-                    ConsoleFormatter::new().with_timestamps(config.include_timestamps),
-                )
-            };
-        }
-
-        macro_rules! json_layer {
-            () => {
-                fmt::layer()
-                    .json()
-                    .with_target(true)
-                    .with_thread_ids(true)
-                    .with_span_events(FmtSpan::CLOSE)
-            };
-        }
-
-        let subscriber = tracing_subscriber::registry().with(env_filter);
-
-        let result = match (config.enable_console, config.enable_json) {
-            (true, true) => subscriber
-                .with(console_layer!())
-                .with(json_layer!())
-                .try_init(),
-            (true, false) => subscriber.with(console_layer!()).try_init(),
-            (false, true) => subscriber.with(json_layer!()).try_init(),
-            (false, false) => subscriber.try_init(),
-        };
-
-        result.map_err(|_| ConfigError::LoggingInitFailed)?;
-        None
+    #[cfg(feature = "sqlite")]
+    let history = match &config.history {
+        Some(history_config) => {
+            let writer = HistoryWriter::start(history_config)?;
+            layers.push(Box::new(writer.layer(redactor.as_ref())));
+            Some(writer)
+        }
+        None => None,
     };
 
-    Ok(guard)
+    layers.push(Box::new(SinkLayer::new(redactor.clone())));
+    layers.push(Box::new(SummaryLayer::new(redactor.clone())));
+
+    if let Some(capacity) = config.memory_buffer {
+        crate::memory::set_capacity(capacity);
+        layers.push(Box::new(MemoryLayer::new(redactor.clone())));
+    }
+
+    // The exporter's HTTP and gRPC clients log every request, which would otherwise
+    // reach the console and every other output.
+    #[cfg(feature = "otlp")]
+    if otlp.is_some() {
+        let filtered = layers.and_then(crate::otlp::exporter_target_filter());
+        layers = vec![Box::new(filtered)];
+    }
+
+    match LOGGING_HANDLES.get() {
+        Some(handles) => {
+            handles
+                .filter
+                .reload(env_filter)
+                .map_err(|e| ConfigError::External(Box::new(e)))?;
+            handles
+                .layers
+                .reload(layers)
+                .map_err(|e| ConfigError::External(Box::new(e)))?;
+        }
+        None => {
+            let (filter_layer, filter) = reload::Layer::new(env_filter);
+            let (layers_layer, layers) = reload::Layer::new(layers);
+
+            let subscriber = tracing_subscriber::registry()
+                .with(filter_layer)
+                .with(layers_layer);
+            // Outside the reloadable layers so that `OpenTelemetrySpanExt` can find it.
+            #[cfg(feature = "otlp")]
+            let subscriber = subscriber.with(crate::otlp::span_layer());
+            subscriber
+                .try_init()
+                .map_err(|_| ConfigError::LoggingInitFailed)?;
+
+            let _ = LOGGING_HANDLES.set(LoggingHandles { filter, layers });
+        }
+    }
+
+    let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    *active = Some(ActiveLogging {
+        generation,
+        default_level,
+        session,
+        chrome_trace,
+        metrics,
+        dedup,
+        #[cfg(feature = "otlp")]
+        otlp,
+        #[cfg(feature = "sqlite")]
+        history,
+        writers: writers.guards,
+    });
+
+    Ok(LoggingGuard {
+        generation: Some(generation),
+    })
 }
 
-pub fn init_logging_default() -> Result<Option<WorkerGuard>, ConfigError> {
+pub fn init_logging_default() -> Result<LoggingGuard, ConfigError> {
     init_logging(LoggingConfig::default())
 }
+
+pub fn set_log_level(level: Level) -> Result<(), ConfigError> {
+    reload_filter(EnvFilter::new(LevelFilter::from_level(level).to_string()))
+}
+
+pub fn set_log_filter(directives: &str) -> Result<(), ConfigError> {
+    let default_level = lock_active()
+        .as_ref()
+        .map(|active| active.default_level)
+        .ok_or(ConfigError::LoggingNotInitialized)?;
+
+    reload_filter(parse_filter(directives, default_level)?)
+}
+
+/// Writes pending repeated-message summaries, blocks until every line buffered by
+/// the async writers has been written out, writes the metrics textfile if one is
+/// configured, exports queued OTLP spans and logs and inserts queued history events.
+pub fn flush_logging() {
+    if let Some(active) = lock_active().as_ref() {
+        active.flush();
+    }
+}
+
+/// Detaches all output layers and drains the async writers. Logging can be
+/// initialized again afterwards.
+pub fn shutdown_logging() {
+    let mut active = lock_active().take();
+
+    // Summaries are written before the async writers they may go through are drained.
+    if let Some(dedup) = active.as_mut().and_then(|active| active.dedup.take()) {
+        dedup.finish();
+    }
+
+    if let Some(handles) = LOGGING_HANDLES.get() {
+        let _ = handles.layers.reload(Vec::new());
+    }
+
+    if let Some(active) = &active {
+        if let Some(session) = &active.session {
+            session.finish();
+        }
+
+        if let Some(chrome_trace) = &active.chrome_trace
+            && let Err(e) = chrome_trace.write()
+        {
+            eprintln!("Failed to write Chrome trace: {}", e);
+        }
+    }
+
+    let dropped: u64 = active
+        .iter()
+        .flat_map(|active| &active.writers)
+        .map(AsyncWriterGuard::dropped_lines)
+        .sum();
+    drop(active);
+
+    if dropped > 0 {
+        eprintln!(
+            "Dropped {} log lines because the async writer buffer was full",
+            dropped
+        );
+    }
+}
+
+fn lock_active() -> MutexGuard<'static, Option<ActiveLogging>> {
+    ACTIVE_LOGGING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+fn reload_filter(filter: EnvFilter) -> Result<(), ConfigError> {
+    if lock_active().is_none() {
+        return Err(ConfigError::LoggingNotInitialized);
+    }
+
+    LOGGING_HANDLES
+        .get()
+        .ok_or(ConfigError::LoggingNotInitialized)?
+        .filter
+        .reload(filter)
+        .map_err(|e| ConfigError::External(Box::new(e)))
+}
+
+pub(crate) fn env_directives() -> Option<String> {
+    LOG_ENV_VARS
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+}
+
+pub(crate) fn parse_filter(
+    directives: &str,
+    default_level: LevelFilter,
+) -> Result<EnvFilter, ConfigError> {
+    EnvFilter::builder()
+        .with_default_directive(default_level.into())
+        .parse(directives)
+        .map_err(|e| ConfigError::InvalidFilter {
+            directives: directives.to_string(),
+            reason: e.to_string(),
+        })
+}
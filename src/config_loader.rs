@@ -0,0 +1,855 @@
+use crate::config::{
+    DedupConfig, FileLogConfig, GelfConfig, HistoryConfig, LogOutput, LogRotation, LoggingConfig,
+    MetricsConfig, NetworkTransport, OtlpConfig, OtlpProtocol, RedactionConfig, SessionConfig,
+    SyslogConfig,
+};
+use crate::error::ConfigError;
+
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use toml::{Table, Value};
+use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+pub const CONFIG_FILE_NAME: &str = "logging.toml";
+pub const ENV_PREFIX: &str = "BAAD_LOG_";
+
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Keys holding a map, written as a TOML table or as `key=value,key=value` in the
+/// environment.
+const MAP_KEYS: &[&str] = &[
+    "json.rename_keys",
+    "json.static_fields",
+    "gelf.static_fields",
+    "dedup.target_limits",
+];
+
+/// Keys holding a list, written as a TOML array or as one entry per line in the
+/// environment. `redaction.fields` also accepts a comma-separated list.
+const LIST_KEYS: &[&str] = &["redaction.fields", "redaction.patterns"];
+
+const KEYS: &[&str] = &[
+    "enable_console",
+    "enable_json",
+    "enable_logfmt",
+    "enable_debug",
+    "verbose_mode",
+    "include_timestamps",
+    "enable_async_writer",
+    "async_writer.buffered_lines_limit",
+    "async_writer.lossy",
+    "filter",
+    "memory_buffer",
+    "chrome_trace",
+    "console_output",
+    "json_output",
+    "logfmt_output",
+    "logfmt_spans",
+    "json.flatten_fields",
+    "json.include_target",
+    "json.include_thread_ids",
+    "json.include_current_span",
+    "json.include_span_list",
+    "json.span_events",
+    "json.rename_keys",
+    "json.static_fields",
+    "file.enabled",
+    "file.directory",
+    "file.file_name_prefix",
+    "file.rotation",
+    "file.max_bytes",
+    "file.max_files",
+    "session.enabled",
+    "session.directory",
+    "session.file_name_prefix",
+    "session.max_sessions",
+    "session.max_age_days",
+    "syslog.enabled",
+    "syslog.transport",
+    "syslog.address",
+    "syslog.facility",
+    "syslog.hostname",
+    "syslog.app_name",
+    "syslog.min_level",
+    "gelf.enabled",
+    "gelf.transport",
+    "gelf.address",
+    "gelf.host",
+    "gelf.static_fields",
+    "gelf.min_level",
+    "dedup.enabled",
+    "dedup.window_ms",
+    "dedup.max_repeats",
+    "dedup.target_limits",
+    "metrics.enabled",
+    "metrics.textfile",
+    "metrics.port",
+    "metrics.interval_ms",
+    "metrics.namespace",
+    "redaction.enabled",
+    "redaction.fields",
+    "redaction.query_params",
+    "redaction.patterns",
+    "otlp.enabled",
+    "otlp.endpoint",
+    "otlp.protocol",
+    "otlp.service_name",
+    "otlp.max_queue_size",
+    "otlp.max_export_batch_size",
+    "otlp.scheduled_delay_ms",
+    "otlp.timeout_ms",
+    "otlp.min_level",
+    "history.enabled",
+    "history.path",
+    "history.batch_size",
+    "history.flush_interval_ms",
+    "history.max_buffered_events",
+    "history.retention_days",
+    "history.min_level",
+];
+
+/// A value as read from `logging.toml` or the environment.
+#[derive(Debug, Clone)]
+enum Setting {
+    Text(String),
+    /// A TOML table, given only for [`MAP_KEYS`].
+    Table(Table),
+}
+
+impl Setting {
+    /// Tables are read by [`parse_map`]; as text they are empty.
+    fn text(&self) -> &str {
+        match self {
+            Setting::Text(text) => text,
+            Setting::Table(_) => "",
+        }
+    }
+}
+
+type Settings = BTreeMap<&'static str, (Setting, ConfigSource)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    Environment,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadedLoggingConfig {
+    pub config: LoggingConfig,
+    pub config_file: Option<PathBuf>,
+    pub sources: BTreeMap<&'static str, ConfigSource>,
+}
+
+impl LoadedLoggingConfig {
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .copied()
+            .unwrap_or(ConfigSource::Default)
+    }
+}
+
+impl LoggingConfig {
+    /// Merges the defaults, `logging.toml` from the config directory and `BAAD_LOG_*`
+    /// environment variables, in increasing order of precedence. The filter is
+    /// overridden by `BAAD_LOG` or `RUST_LOG` instead, as in `init_logging`.
+    pub fn load() -> Result<LoadedLoggingConfig, ConfigError> {
+        let path = crate::file::get_config_path(CONFIG_FILE_NAME)
+            .map_err(|e| ConfigError::External(Box::new(e)))?;
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<LoadedLoggingConfig, ConfigError> {
+        let mut values = Settings::new();
+
+        let config_file = if path.is_file() {
+            for (key, value) in read_config_file(path)? {
+                values.insert(key, (value, ConfigSource::File));
+            }
+            Some(path.to_path_buf())
+        } else {
+            None
+        };
+
+        for key in KEYS.iter().filter(|key| **key != "filter") {
+            if let Ok(value) = env::var(env_var_name(key)) {
+                values.insert(key, (Setting::Text(value), ConfigSource::Environment));
+            }
+        }
+        if let Some(directives) = crate::config::env_directives() {
+            values.insert(
+                "filter",
+                (Setting::Text(directives), ConfigSource::Environment),
+            );
+        }
+
+        let mut config = LoggingConfig::default();
+        for (key, (setting, _)) in &values {
+            apply(&mut config, key, setting)?;
+        }
+
+        if section_enabled(&values, "file")? {
+            config.file = Some(file_config(&values)?);
+        }
+        if section_enabled(&values, "session")? {
+            config.session = Some(session_config(&values)?);
+        }
+        if section_enabled(&values, "syslog")? {
+            config.syslog = Some(syslog_config(&values)?);
+        }
+        if section_enabled(&values, "gelf")? {
+            config.gelf = Some(gelf_config(&values)?);
+        }
+        if section_enabled(&values, "dedup")? {
+            config.dedup = Some(dedup_config(&values)?);
+        }
+        if section_enabled(&values, "metrics")? {
+            config.metrics = Some(metrics_config(&values)?);
+        }
+        if section_enabled(&values, "redaction")? {
+            config.redaction = Some(redaction_config(&values)?);
+        }
+        if section_enabled(&values, "otlp")? {
+            config.otlp = Some(otlp_config(&values)?);
+        }
+        if section_enabled(&values, "history")? {
+            config.history = Some(history_config(&values)?);
+        }
+
+        let sources = KEYS
+            .iter()
+            .map(|key| {
+                let source = values
+                    .get(key)
+                    .map(|(_, source)| *source)
+                    .unwrap_or(ConfigSource::Default);
+                (*key, source)
+            })
+            .collect();
+
+        Ok(LoadedLoggingConfig {
+            config,
+            config_file,
+            sources,
+        })
+    }
+}
+
+pub fn env_var_name(key: &str) -> String {
+    format!("{ENV_PREFIX}{}", key.replace('.', "_").to_uppercase())
+}
+
+fn read_config_file(path: &Path) -> Result<Vec<(&'static str, Setting)>, ConfigError> {
+    let content = fs::read_to_string(path).map_err(|e| ConfigError::External(Box::new(e)))?;
+    let table: Table = content
+        .parse()
+        .map_err(|e: toml::de::Error| ConfigError::ConfigFile {
+            path: path.display().to_string(),
+            reason: e.message().to_string(),
+        })?;
+
+    let mut entries = Vec::new();
+    flatten(path, "", &table, &mut entries)?;
+    Ok(entries)
+}
+
+fn flatten(
+    path: &Path,
+    prefix: &str,
+    table: &Table,
+    entries: &mut Vec<(&'static str, Setting)>,
+) -> Result<(), ConfigError> {
+    for (name, value) in table {
+        let full_key = format!("{prefix}{name}");
+
+        if let Value::Table(nested) = value
+            && !MAP_KEYS.contains(&full_key.as_str())
+        {
+            flatten(path, &format!("{full_key}."), nested, entries)?;
+            continue;
+        }
+
+        let key =
+            KEYS.iter()
+                .find(|key| **key == full_key)
+                .ok_or_else(|| ConfigError::ConfigFile {
+                    path: path.display().to_string(),
+                    reason: format!("unknown key `{full_key}`"),
+                })?;
+
+        if MAP_KEYS.contains(key) && !value.is_table() {
+            return Err(ConfigError::ConfigFile {
+                path: path.display().to_string(),
+                reason: format!("`{full_key}` must be a table"),
+            });
+        }
+
+        let value = match value {
+            Value::String(s) => Setting::Text(s.clone()),
+            Value::Integer(i) => Setting::Text(i.to_string()),
+            Value::Boolean(b) => Setting::Text(b.to_string()),
+            Value::Table(map) => Setting::Table(map.clone()),
+            Value::Array(items) if LIST_KEYS.contains(&full_key.as_str()) => Setting::Text(
+                items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            other => {
+                return Err(ConfigError::ConfigFile {
+                    path: path.display().to_string(),
+                    reason: format!("unsupported value for `{full_key}`: {other}"),
+                });
+            }
+        };
+
+        entries.push((key, value));
+    }
+
+    Ok(())
+}
+
+fn apply(config: &mut LoggingConfig, key: &str, setting: &Setting) -> Result<(), ConfigError> {
+    let value = setting.text();
+    match key {
+        "enable_console" => config.enable_console = parse_bool(key, value)?,
+        "enable_json" => config.enable_json = parse_bool(key, value)?,
+        "enable_logfmt" => config.enable_logfmt = parse_bool(key, value)?,
+        "enable_debug" => config.enable_debug = parse_bool(key, value)?,
+        "verbose_mode" => config.verbose_mode = parse_bool(key, value)?,
+        "include_timestamps" => config.include_timestamps = parse_bool(key, value)?,
+        "enable_async_writer" => config.enable_async_writer = parse_bool(key, value)?,
+        "async_writer.buffered_lines_limit" => {
+            config.async_writer.buffered_lines_limit = parse_value(key, value)?;
+            if config.async_writer.buffered_lines_limit == 0 {
+                return Err(invalid_value(key, value, "must be at least 1"));
+            }
+        }
+        "async_writer.lossy" => config.async_writer.lossy = parse_bool(key, value)?,
+        "filter" => config.filter = Some(value.to_string()),
+        "memory_buffer" => {
+            config.memory_buffer = Some(parse_value::<usize>(key, value)?).filter(|n| *n > 0)
+        }
+        "chrome_trace" => config.chrome_trace = Some(PathBuf::from(value)),
+        "console_output" => config.console_output = parse_output(key, value)?,
+        "json_output" => config.json_output = parse_output(key, value)?,
+        "logfmt_output" => config.logfmt_output = parse_output(key, value)?,
+        "logfmt_spans" => config.logfmt_spans = parse_bool(key, value)?,
+        "json.flatten_fields" => config.json.flatten_fields = parse_bool(key, value)?,
+        "json.include_target" => config.json.include_target = parse_bool(key, value)?,
+        "json.include_thread_ids" => config.json.include_thread_ids = parse_bool(key, value)?,
+        "json.include_current_span" => config.json.include_current_span = parse_bool(key, value)?,
+        "json.include_span_list" => config.json.include_span_list = parse_bool(key, value)?,
+        "json.span_events" => config.json.span_events = parse_span_events(key, value)?,
+        "json.rename_keys" => config.json.rename_keys = parse_map(key, setting)?,
+        "json.static_fields" => config.json.static_fields = parse_map(key, setting)?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// A section such as `file` is enabled by any of its keys unless `<section>.enabled`
+/// says otherwise.
+fn section_enabled(values: &Settings, section: &str) -> Result<bool, ConfigError> {
+    let enabled_key = format!("{section}.enabled");
+    match values.get(enabled_key.as_str()) {
+        Some((setting, _)) => parse_bool(&enabled_key, setting.text()),
+        None => Ok(values.keys().any(|key| {
+            key.strip_prefix(section)
+                .is_some_and(|rest| rest.starts_with('.'))
+        })),
+    }
+}
+
+fn file_config(values: &Settings) -> Result<FileLogConfig, ConfigError> {
+    let mut file = FileLogConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "file.directory" => file.directory = Some(PathBuf::from(value)),
+            "file.file_name_prefix" => file.file_name_prefix = value.to_string(),
+            "file.rotation" => file.rotation = parse_rotation(key, value)?,
+            "file.max_files" => file.max_files = parse_value(key, value)?,
+            _ => {}
+        }
+    }
+
+    if let LogRotation::Size { max_bytes } = &mut file.rotation
+        && let Some((setting, _)) = values.get("file.max_bytes")
+    {
+        *max_bytes = parse_value("file.max_bytes", setting.text())?;
+    }
+
+    Ok(file)
+}
+
+fn session_config(values: &Settings) -> Result<SessionConfig, ConfigError> {
+    let mut session = SessionConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "session.directory" => session.directory = Some(PathBuf::from(value)),
+            "session.file_name_prefix" => session.file_name_prefix = value.to_string(),
+            "session.max_sessions" => session.max_sessions = parse_value(key, value)?,
+            "session.max_age_days" => {
+                session.max_age_days = Some(parse_value::<u64>(key, value)?).filter(|d| *d > 0)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(session)
+}
+
+fn syslog_config(values: &Settings) -> Result<SyslogConfig, ConfigError> {
+    let mut syslog = SyslogConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "syslog.facility" => syslog.facility = parse_value(key, value)?,
+            "syslog.hostname" => syslog.hostname = Some(value.to_string()),
+            "syslog.app_name" => syslog.app_name = Some(value.to_string()),
+            "syslog.min_level" => syslog.min_level = parse_value::<Level>(key, value)?,
+            _ => {}
+        }
+    }
+
+    if let Some(transport) = parse_transport(values, "syslog", 514)? {
+        syslog.transport = transport;
+    }
+
+    Ok(syslog)
+}
+
+fn gelf_config(values: &Settings) -> Result<GelfConfig, ConfigError> {
+    let mut gelf = GelfConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "gelf.host" => gelf.host = Some(value.to_string()),
+            "gelf.static_fields" => gelf.static_fields = parse_map(key, setting)?,
+            "gelf.min_level" => gelf.min_level = parse_value::<Level>(key, value)?,
+            _ => {}
+        }
+    }
+
+    if let Some(transport) = parse_transport(values, "gelf", 12201)? {
+        gelf.transport = transport;
+    }
+
+    Ok(gelf)
+}
+
+fn dedup_config(values: &Settings) -> Result<DedupConfig, ConfigError> {
+    let mut dedup = DedupConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "dedup.window_ms" => dedup.window = Duration::from_millis(parse_value(key, value)?),
+            "dedup.max_repeats" => dedup.max_repeats = parse_value(key, value)?,
+            "dedup.target_limits" => dedup.target_limits = parse_map(key, setting)?,
+            _ => {}
+        }
+    }
+
+    Ok(dedup)
+}
+
+fn metrics_config(values: &Settings) -> Result<MetricsConfig, ConfigError> {
+    let mut metrics = MetricsConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "metrics.textfile" => metrics.textfile = Some(PathBuf::from(value)),
+            "metrics.port" => metrics.port = Some(parse_value(key, value)?),
+            "metrics.interval_ms" => {
+                metrics.interval = Duration::from_millis(parse_value(key, value)?)
+            }
+            "metrics.namespace" => metrics.namespace = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    Ok(metrics)
+}
+
+fn redaction_config(values: &Settings) -> Result<RedactionConfig, ConfigError> {
+    let mut redaction = RedactionConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "redaction.fields" => {
+                redaction.fields = parse_list(value, &['\n', ','])
+                    .map(str::to_string)
+                    .collect();
+            }
+            "redaction.query_params" => redaction.query_params = parse_bool(key, value)?,
+            "redaction.patterns" => {
+                redaction.patterns = parse_list(value, &['\n']).map(str::to_string).collect();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(redaction)
+}
+
+fn otlp_config(values: &Settings) -> Result<OtlpConfig, ConfigError> {
+    let mut otlp = OtlpConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "otlp.endpoint" => otlp.endpoint = Some(value.to_string()),
+            "otlp.protocol" => {
+                otlp.protocol = match value.trim().to_ascii_lowercase().as_str() {
+                    "http" | "http/protobuf" => OtlpProtocol::HttpProtobuf,
+                    "grpc" => OtlpProtocol::Grpc,
+                    _ => return Err(invalid_value(key, value, "expected http or grpc")),
+                };
+            }
+            "otlp.service_name" => otlp.service_name = Some(value.to_string()),
+            "otlp.max_queue_size" => otlp.max_queue_size = parse_value(key, value)?,
+            "otlp.max_export_batch_size" => otlp.max_export_batch_size = parse_value(key, value)?,
+            "otlp.scheduled_delay_ms" => {
+                otlp.scheduled_delay = Duration::from_millis(parse_value(key, value)?)
+            }
+            "otlp.timeout_ms" => otlp.timeout = Duration::from_millis(parse_value(key, value)?),
+            "otlp.min_level" => otlp.min_level = parse_value::<Level>(key, value)?,
+            _ => {}
+        }
+    }
+
+    Ok(otlp)
+}
+
+/// `history.retention_days` of 0 keeps events forever.
+fn history_config(values: &Settings) -> Result<HistoryConfig, ConfigError> {
+    let mut history = HistoryConfig::default();
+
+    for (key, (setting, _)) in values {
+        let value = setting.text();
+        match *key {
+            "history.path" => history.path = Some(PathBuf::from(value)),
+            "history.batch_size" => {
+                history.batch_size = parse_value(key, value)?;
+                if history.batch_size == 0 {
+                    return Err(invalid_value(key, value, "must be at least 1"));
+                }
+            }
+            "history.flush_interval_ms" => {
+                history.flush_interval = Duration::from_millis(parse_value(key, value)?)
+            }
+            "history.max_buffered_events" => {
+                history.max_buffered_events = parse_value(key, value)?;
+                if history.max_buffered_events == 0 {
+                    return Err(invalid_value(key, value, "must be at least 1"));
+                }
+            }
+            "history.retention_days" => {
+                history.retention = match parse_value::<u64>(key, value)? {
+                    0 => None,
+                    days => Some(Duration::from_secs(days * 24 * 60 * 60)),
+                };
+            }
+            "history.min_level" => history.min_level = parse_value::<Level>(key, value)?,
+            _ => {}
+        }
+    }
+
+    Ok(history)
+}
+
+/// Combines `<section>.transport` (udp, tcp or unix) with `<section>.address`. UDP and
+/// TCP default to `127.0.0.1:<default_port>`; unix requires an address.
+fn parse_transport(
+    values: &Settings,
+    section: &str,
+    default_port: u16,
+) -> Result<Option<NetworkTransport>, ConfigError> {
+    let transport_key = format!("{section}.transport");
+    let address_key = format!("{section}.address");
+    let transport = values
+        .get(transport_key.as_str())
+        .map(|(setting, _)| setting.text());
+    let address = values
+        .get(address_key.as_str())
+        .map(|(setting, _)| setting.text().to_string());
+
+    if transport.is_none() && address.is_none() {
+        return Ok(None);
+    }
+
+    let default_address = || format!("127.0.0.1:{default_port}");
+    let transport = match transport
+        .map(|value| value.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        None | Some("udp") => NetworkTransport::Udp(address.unwrap_or_else(default_address)),
+        Some("tcp") => NetworkTransport::Tcp(address.unwrap_or_else(default_address)),
+        Some("unix") => NetworkTransport::Unix(PathBuf::from(
+            address.ok_or_else(|| invalid_value(&address_key, "", "required for unix"))?,
+        )),
+        Some(value) => {
+            return Err(invalid_value(
+                &transport_key,
+                value,
+                "expected udp, tcp or unix",
+            ));
+        }
+    };
+
+    Ok(Some(transport))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ConfigError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err(invalid_value(key, value, "expected a boolean")),
+    }
+}
+
+fn parse_value<T>(key: &str, value: &str) -> Result<T, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e: T::Err| invalid_value(key, value, &e.to_string()))
+}
+
+/// Files are given as `file:<path>`, or as a bare path if it contains a directory
+/// separator or has an extension, so a misspelt `stdout` is an error rather than a
+/// new file.
+fn parse_output(key: &str, value: &str) -> Result<LogOutput, ConfigError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "stdout" => return Ok(LogOutput::Stdout),
+        "stderr" => return Ok(LogOutput::Stderr),
+        "split" => return Ok(LogOutput::Split),
+        _ => {}
+    }
+
+    if let Some(path) = value.strip_prefix("file:") {
+        if path.is_empty() {
+            return Err(invalid_value(key, value, "expected a path after `file:`"));
+        }
+        return Ok(LogOutput::File(PathBuf::from(path)));
+    }
+
+    let path = Path::new(value);
+    if value.contains(['/', '\\']) || path.extension().is_some() {
+        return Ok(LogOutput::File(path.to_path_buf()));
+    }
+
+    Err(invalid_value(
+        key,
+        value,
+        "expected stdout, stderr, split or a file path such as `file:app.log`",
+    ))
+}
+
+fn parse_span_events(key: &str, value: &str) -> Result<FmtSpan, ConfigError> {
+    value
+        .split(',')
+        .map(|event| event.trim().to_ascii_lowercase())
+        .filter(|event| !event.is_empty())
+        .try_fold(FmtSpan::NONE, |events, event| {
+            let event = match event.as_str() {
+                "none" => FmtSpan::NONE,
+                "new" => FmtSpan::NEW,
+                "enter" => FmtSpan::ENTER,
+                "exit" => FmtSpan::EXIT,
+                "close" => FmtSpan::CLOSE,
+                "active" => FmtSpan::ACTIVE,
+                "full" => FmtSpan::FULL,
+                _ => {
+                    return Err(invalid_value(
+                        key,
+                        value,
+                        "expected a list of new, enter, exit, close, active, full or none",
+                    ));
+                }
+            };
+            Ok(events | event)
+        })
+}
+
+/// Tables from `logging.toml` are deserialized as they are; the environment gives
+/// `key=value,key=value`, so there keys and values cannot contain `,` or `=`.
+fn parse_map<T>(key: &str, setting: &Setting) -> Result<BTreeMap<String, T>, ConfigError>
+where
+    T: DeserializeOwned + FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = match setting {
+        Setting::Table(table) => {
+            return Value::Table(table.clone())
+                .try_into()
+                .map_err(|e: toml::de::Error| invalid_value(key, &table.to_string(), e.message()));
+        }
+        Setting::Text(value) => value,
+    };
+
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (k, v) = entry
+                .split_once('=')
+                .ok_or_else(|| invalid_value(key, value, "expected key=value pairs"))?;
+            Ok((k.trim().to_string(), parse_value(key, v)?))
+        })
+        .collect()
+}
+
+fn parse_list<'a>(value: &'a str, separators: &[char]) -> impl Iterator<Item = &'a str> {
+    value
+        .split(separators)
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+fn parse_rotation(key: &str, value: &str) -> Result<LogRotation, ConfigError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "daily" => Ok(LogRotation::Daily),
+        "hourly" => Ok(LogRotation::Hourly),
+        "size" => Ok(LogRotation::Size {
+            max_bytes: DEFAULT_MAX_BYTES,
+        }),
+        _ => Err(invalid_value(key, value, "expected daily, hourly or size")),
+    }
+}
+
+fn invalid_value(key: &str, value: &str, reason: &str) -> ConfigError {
+    ConfigError::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_toml(name: &str, content: &str) -> Result<LoadedLoggingConfig, ConfigError> {
+        let path = env::temp_dir().join(format!("baad-{}-{name}.toml", std::process::id()));
+        fs::write(&path, content).unwrap();
+        let loaded = LoggingConfig::load_from(&path);
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn tables_keep_separators_in_values() {
+        let loaded = load_toml(
+            "tables",
+            r#"
+            [json.static_fields]
+            build = "a=b,c"
+
+            [dedup.target_limits]
+            "hyper::proto" = 3
+            "#,
+        )
+        .unwrap();
+
+        let static_fields = &loaded.config.json.static_fields;
+        assert_eq!(
+            static_fields.get("build").map(String::as_str),
+            Some("a=b,c")
+        );
+        let dedup = loaded.config.dedup.as_ref().unwrap();
+        assert_eq!(dedup.target_limits.get("hyper::proto"), Some(&3));
+        assert_eq!(loaded.source("json.static_fields"), ConfigSource::File);
+        assert_eq!(loaded.source("json.rename_keys"), ConfigSource::Default);
+    }
+
+    #[test]
+    fn rejects_maps_written_as_strings_and_unknown_keys() {
+        let error = load_toml("string-map", r#"json.static_fields = "a=b""#).unwrap_err();
+        assert!(error.to_string().contains("must be a table"), "{error}");
+
+        let error = load_toml("unknown", "enable_colour = true").unwrap_err();
+        assert!(error.to_string().contains("enable_colour"), "{error}");
+
+        let error = load_toml("bad-limit", "dedup.target_limits = { hyper = \"x\" }").unwrap_err();
+        assert!(
+            matches!(error, ConfigError::InvalidValue { ref key, .. } if key == "dedup.target_limits"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn environment_maps_are_key_value_pairs() {
+        let setting = Setting::Text("hyper=3, tonic = 5,".to_string());
+        let limits: BTreeMap<String, u32> = parse_map("dedup.target_limits", &setting).unwrap();
+        assert_eq!(
+            limits,
+            BTreeMap::from([("hyper".to_string(), 3), ("tonic".to_string(), 5)])
+        );
+
+        let setting = Setting::Text("hyper".to_string());
+        assert!(parse_map::<String>("json.rename_keys", &setting).is_err());
+    }
+
+    #[test]
+    fn filter_source_follows_the_env_override() {
+        let loaded = load_toml("filter", r#"filter = "baad=trace""#).unwrap();
+
+        match crate::config::env_directives() {
+            Some(directives) => {
+                assert_eq!(loaded.config.filter, Some(directives));
+                assert_eq!(loaded.source("filter"), ConfigSource::Environment);
+            }
+            None => {
+                assert_eq!(loaded.config.filter.as_deref(), Some("baad=trace"));
+                assert_eq!(loaded.source("filter"), ConfigSource::File);
+            }
+        }
+    }
+
+    #[test]
+    fn sections_are_enabled_by_their_keys() {
+        let loaded = load_toml(
+            "sections",
+            r#"
+            file.rotation = "size"
+            file.max_bytes = 1024
+
+            [syslog]
+            enabled = false
+            address = "10.0.0.1:514"
+            "#,
+        )
+        .unwrap();
+
+        let file = loaded.config.file.unwrap();
+        assert_eq!(file.rotation, LogRotation::Size { max_bytes: 1024 });
+        assert!(loaded.config.syslog.is_none());
+        assert!(loaded.config.gelf.is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use pin_project_lite::pin_project;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::task::JoinHandle;
+
+thread_local! {
+    static CURRENT: RefCell<LogContext> = RefCell::new(LogContext::default());
+}
+
+/// Fields such as `job_id` that are added to every event logged while the context is
+/// entered. Contexts nest: [`LogContext::with`] on the current context keeps its
+/// fields and adds or replaces one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogContext {
+    fields: Arc<Vec<(String, String)>>,
+}
+
+impl LogContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current() -> Self {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    pub fn with(mut self, key: impl Into<String>, value: impl fmt::Display) -> Self {
+        let key = key.into();
+        let value = value.to_string();
+        let fields = Arc::make_mut(&mut self.fields);
+
+        match fields.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, existing)) => *existing = value,
+            None => fields.push((key, value)),
+        }
+        self
+    }
+
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Makes this the current context on this thread until the guard is dropped.
+    pub fn enter(&self) -> ContextGuard {
+        let previous = CURRENT.with(|current| current.replace(self.clone()));
+        ContextGuard {
+            previous: Some(previous),
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.enter();
+        f()
+    }
+
+    /// Enters this context every time `future` is polled, whichever thread polls it.
+    pub fn wrap<F: Future>(self, future: F) -> WithContext<F> {
+        WithContext {
+            context: self,
+            future,
+        }
+    }
+}
+
+/// Restores the previously current context when dropped.
+pub struct ContextGuard {
+    previous: Option<LogContext>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+}
+
+pin_project! {
+    pub struct WithContext<F> {
+        context: LogContext,
+        #[pin]
+        future: F,
+    }
+}
+
+impl<F: Future> Future for WithContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _guard = this.context.enter();
+        this.future.poll(cx)
+    }
+}
+
+/// Like [`tokio::spawn`], but the task keeps the caller's current context.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(LogContext::current().wrap(future))
+}
+
+/// Like [`tokio::task::spawn_blocking`], but the closure keeps the caller's current
+/// context.
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let context = LogContext::current();
+    tokio::task::spawn_blocking(move || context.scope(f))
+}
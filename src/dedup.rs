@@ -0,0 +1,457 @@
+use crate::async_writer::SharedMakeWriter;
+use crate::config::DedupConfig;
+use crate::error::ConfigError;
+use crate::formatter::{ConsoleFormatter, FieldCollector};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, bounded};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::io::Write as _;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::callsite::{DefaultCallsite, Identifier};
+use tracing::field::FieldSet;
+use tracing::metadata::Kind;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+
+macro_rules! summary_callsite {
+    ($callsite:ident, $metadata:ident, $level:expr) => {
+        static $callsite: DefaultCallsite = DefaultCallsite::new(&$metadata);
+        static $metadata: Metadata<'static> = Metadata::new(
+            "repeated message summary",
+            "baad_core::dedup",
+            $level,
+            Some(file!()),
+            Some(line!()),
+            Some(module_path!()),
+            FieldSet::new(&["message"], Identifier(&$callsite)),
+            Kind::EVENT,
+        );
+    };
+}
+
+summary_callsite!(ERROR_CALLSITE, ERROR_SUMMARY, Level::ERROR);
+summary_callsite!(WARN_CALLSITE, WARN_SUMMARY, Level::WARN);
+summary_callsite!(INFO_CALLSITE, INFO_SUMMARY, Level::INFO);
+summary_callsite!(DEBUG_CALLSITE, DEBUG_SUMMARY, Level::DEBUG);
+summary_callsite!(TRACE_CALLSITE, TRACE_SUMMARY, Level::TRACE);
+
+/// Flushes the summaries of ended windows every `window`, so that a burst is
+/// reported even if nothing is logged after it.
+pub(crate) struct DedupFlusher {
+    dedup: Arc<Dedup>,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DedupFlusher {
+    pub(crate) fn start(dedup: Arc<Dedup>) -> Result<Self, ConfigError> {
+        let (stop, stopped): (Sender<()>, Receiver<()>) = bounded(0);
+        let thread_dedup = dedup.clone();
+        let window = dedup.config.window;
+        let handle = thread::Builder::new()
+            .name("baad-dedup".to_string())
+            .spawn(move || {
+                while stopped.recv_timeout(window) == Err(RecvTimeoutError::Timeout) {
+                    thread_dedup.flush(false);
+                }
+            })
+            .map_err(|e| ConfigError::External(Box::new(e)))?;
+
+        Ok(Self {
+            dedup,
+            stop: Some(stop),
+            handle: Some(handle),
+        })
+    }
+
+    /// Writes the summaries of every message with suppressed repeats.
+    pub(crate) fn flush(&self) {
+        self.dedup.flush(true);
+    }
+
+    /// Stops the thread and writes every pending summary.
+    pub(crate) fn finish(mut self) {
+        self.stop_thread();
+        self.flush();
+    }
+
+    fn stop_thread(&mut self) {
+        // Disconnecting the channel tells the thread to stop.
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for DedupFlusher {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
+
+fn summary_metadata(level: Level) -> &'static Metadata<'static> {
+    match level {
+        Level::ERROR => &ERROR_SUMMARY,
+        Level::WARN => &WARN_SUMMARY,
+        Level::INFO => &INFO_SUMMARY,
+        Level::DEBUG => &DEBUG_SUMMARY,
+        Level::TRACE => &TRACE_SUMMARY,
+    }
+}
+
+type Key = (Identifier, String);
+
+struct Entry {
+    level: Level,
+    window_start: Instant,
+    count: u32,
+    suppressed: u64,
+}
+
+struct Summary {
+    level: Level,
+    message: String,
+}
+
+impl Summary {
+    fn new(entry: &Entry, message: &str) -> Self {
+        Self {
+            level: entry.level,
+            message: format!(
+                "previous message repeated {} times: {}",
+                entry.suppressed, message
+            ),
+        }
+    }
+}
+
+struct DedupState {
+    entries: HashMap<Key, Entry>,
+    last_sweep: Instant,
+}
+
+impl DedupState {
+    /// Removes entries whose window has ended, returning summaries for those with
+    /// suppressed repeats. With `all`, the repeats suppressed so far in windows that
+    /// are still running are summarized too, and counting starts again from zero.
+    fn take_summaries(&mut self, now: Instant, window: Duration, all: bool) -> Vec<Summary> {
+        let mut summaries = Vec::new();
+        self.last_sweep = now;
+        self.entries.retain(|(_, message), entry| {
+            let ended = now.duration_since(entry.window_start) >= window;
+            if entry.suppressed > 0 && (ended || all) {
+                summaries.push(Summary::new(entry, message));
+                entry.suppressed = 0;
+            }
+            !ended
+        });
+        summaries
+    }
+}
+
+/// Counts repeats for a [`DedupLayer`] and writes its summaries, with the writer and
+/// formatter of the console layer it wraps. Shared with the [`DedupFlusher`], which
+/// writes summaries when nothing is being logged.
+pub struct Dedup {
+    config: DedupConfig,
+    state: Mutex<DedupState>,
+    writer: SharedMakeWriter,
+    formatter: ConsoleFormatter,
+    ansi: bool,
+}
+
+impl Dedup {
+    pub fn new(
+        config: DedupConfig,
+        writer: SharedMakeWriter,
+        formatter: ConsoleFormatter,
+        ansi: bool,
+    ) -> Self {
+        Self {
+            config,
+            state: Mutex::new(DedupState {
+                entries: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+            writer,
+            formatter,
+            ansi,
+        }
+    }
+
+    /// Returns whether the event should be passed on, along with summaries for
+    /// messages whose window has ended.
+    fn check(&self, event: &Event<'_>) -> (bool, Vec<Summary>) {
+        let metadata = event.metadata();
+        let limit = self.config.limit_for(metadata.target());
+        if limit == 0 {
+            return (true, Vec::new());
+        }
+
+        let mut visitor = FieldCollector::new();
+        event.record(&mut visitor);
+        let message = visitor
+            .fields
+            .into_iter()
+            .find(|(name, _)| *name == "message")
+            .map(|(_, message)| message.into_owned())
+            .unwrap_or_default();
+
+        let now = Instant::now();
+        let window = self.config.window;
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut summaries = Vec::new();
+
+        if now.duration_since(state.last_sweep) >= window {
+            summaries = state.take_summaries(now, window, false);
+        }
+
+        let entry = state
+            .entries
+            .entry((metadata.callsite(), message.clone()))
+            .or_insert_with(|| Entry {
+                level: *metadata.level(),
+                window_start: now,
+                count: 0,
+                suppressed: 0,
+            });
+
+        if now.duration_since(entry.window_start) >= window {
+            if entry.suppressed > 0 {
+                summaries.push(Summary::new(entry, &message));
+            }
+            entry.window_start = now;
+            entry.count = 0;
+            entry.suppressed = 0;
+        }
+
+        entry.count = entry.count.saturating_add(1);
+        let forward = entry.count <= limit;
+        if !forward {
+            entry.suppressed += 1;
+        }
+
+        (forward, summaries)
+    }
+
+    /// Writes the summaries of messages whose window has ended, or of every message
+    /// with suppressed repeats when `all` is set.
+    pub(crate) fn flush(&self, all: bool) {
+        let summaries = self
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take_summaries(Instant::now(), self.config.window, all);
+        self.write_summaries(summaries);
+    }
+
+    fn write_summaries(&self, summaries: Vec<Summary>) {
+        for summary in summaries {
+            let line = self
+                .formatter
+                .format_message(&summary.level, summary.message, self.ansi);
+            let mut writer = self.writer.make_writer_for(summary_metadata(summary.level));
+            let _ = writer.write_all(line.as_bytes());
+        }
+    }
+}
+
+/// Wraps an output layer and drops identical messages (same callsite and text) once
+/// they have been seen `max_repeats` times within `window`. The number dropped is
+/// reported as "previous message repeated N times" once the window has passed, when
+/// logging is flushed and at shutdown.
+pub struct DedupLayer<S> {
+    inner: Box<dyn Layer<S> + Send + Sync>,
+    dedup: Arc<Dedup>,
+}
+
+impl<S> DedupLayer<S> {
+    pub fn new(inner: Box<dyn Layer<S> + Send + Sync>, dedup: Arc<Dedup>) -> Self {
+        Self { inner, dedup }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for DedupLayer<S> {
+    fn on_register_dispatch(&self, subscriber: &tracing::Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.inner.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.enabled(metadata, ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(id, values, ctx);
+    }
+
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: Context<'_, S>) {
+        self.inner.on_follows_from(id, follows, ctx);
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.event_enabled(event, ctx)
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let (forward, summaries) = self.dedup.check(event);
+        self.dedup.write_summaries(summaries);
+
+        if forward {
+            self.inner.on_event(event, ctx);
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx);
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        self.inner.on_id_change(old, new, ctx);
+    }
+
+    #[doc(hidden)]
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            Some(self as *const _ as *const ())
+        } else {
+            unsafe { self.inner.downcast_raw(id) }
+        }
+    }
+}
+
+impl DedupConfig {
+    /// The most specific `target_limits` prefix wins over `max_repeats`.
+    fn limit_for(&self, target: &str) -> u32 {
+        self.target_limits
+            .iter()
+            .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, limit)| *limit)
+            .unwrap_or(self.max_repeats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::io;
+    use tracing_subscriber::fmt;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry::Registry;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn lines(buffer: &Buffer) -> Vec<String> {
+        String::from_utf8(buffer.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Runs `log` with a deduplicated console layer, then flushes every summary.
+    fn run(config: DedupConfig, log: impl FnOnce()) -> Vec<String> {
+        let buffer = Buffer::default();
+        let writer = {
+            let buffer = buffer.clone();
+            SharedMakeWriter::new(move || buffer.clone())
+        };
+        let formatter = ConsoleFormatter::new();
+        let dedup = Arc::new(Dedup::new(config, writer.clone(), formatter.clone(), false));
+        let console = fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .event_format(formatter);
+        let layer = DedupLayer::<Registry>::new(Box::new(console), dedup.clone());
+
+        tracing::subscriber::with_default(Registry::default().with(layer), log);
+        dedup.flush(true);
+        lines(&buffer)
+    }
+
+    #[test]
+    fn summarizes_suppressed_repeats_on_flush() {
+        let config = DedupConfig {
+            window: Duration::from_secs(60),
+            max_repeats: 2,
+            target_limits: BTreeMap::new(),
+        };
+        let lines = run(config, || {
+            for _ in 0..5 {
+                tracing::warn!("disk almost full");
+            }
+            tracing::info!("done");
+        });
+
+        assert_eq!(
+            lines,
+            [
+                "   [WARN] disk almost full",
+                "   [WARN] disk almost full",
+                "   [INFO] done",
+                "   [WARN] previous message repeated 3 times: disk almost full",
+            ]
+        );
+    }
+
+    #[test]
+    fn target_limit_of_zero_turns_deduplication_off() {
+        let config = DedupConfig {
+            window: Duration::from_secs(60),
+            max_repeats: 1,
+            target_limits: BTreeMap::from([(module_path!().to_string(), 0)]),
+        };
+        let lines = run(config, || {
+            for _ in 0..3 {
+                tracing::info!("polling");
+            }
+        });
+
+        assert_eq!(lines.len(), 3, "{lines:?}");
+    }
+}
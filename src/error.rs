@@ -1,7 +1,6 @@
-use eyre::{EyreHandler, Report};
-use std::sync::Once;
+use eyre::{EyreHandler, InstallError, Report};
+use std::sync::{Once, OnceLock};
 use thiserror::Error;
-use tracing::{error, warn};
 
 pub trait IntoEyreReport {
     fn into_eyre_report(self) -> Report;
@@ -39,12 +38,12 @@ impl EyreHandler for TracingHandler {
         }
 
         let mut prev_msg = error.to_string();
-        error!("{}", error);
+        crate::error!("{}", error);
 
         for cause in std::iter::successors(error.source(), |e| (*e).source()) {
             let cause_msg = cause.to_string();
             if cause_msg != prev_msg {
-                error!("{}", cause);
+                crate::error!("{}", cause);
             }
             prev_msg = cause_msg;
         }
@@ -56,28 +55,32 @@ impl EyreHandler for TracingHandler {
 pub fn log_recoverable_error(error: &Report, recovery_action: &str) {
     if let Some(cause) = error.source() {
         if cause.to_string() == error.to_string() {
-            warn!(
+            crate::warn!(
                 recovery = recovery_action,
-                "Recoverable error, continuing: {}", error
+                "Recoverable error, continuing: {}",
+                error
             );
         } else {
-            warn!(
+            crate::warn!(
                 cause = %cause,
                 recovery = recovery_action,
                 "Recoverable error, continuing: {}", error
             );
         }
     } else {
-        warn!(
+        crate::warn!(
             recovery = recovery_action,
-            "Recoverable error, continuing: {}", error
+            "Recoverable error, continuing: {}",
+            error
         );
     }
 }
 
 pub fn install() -> Result<(), ConfigError> {
-    eyre::set_hook(Box::new(|_| Box::new(TracingHandler::new())))
-        .map_err(|e| ConfigError::External(Box::new(e)))?;
+    // Every call reports whether the first one installed the hook.
+    static HOOK: OnceLock<Result<(), InstallError>> = OnceLock::new();
+    let result = HOOK.get_or_init(|| eyre::set_hook(Box::new(|_| Box::new(TracingHandler::new()))));
+    result.map_err(|e| ConfigError::External(Box::new(e)))?;
 
     static ONCE: Once = Once::new();
     ONCE.call_once(|| {
@@ -95,7 +98,7 @@ pub fn install() -> Result<(), ConfigError> {
                 .map(|loc| format!("{}:{}:{}", loc.file(), loc.line(), loc.column()))
                 .unwrap_or_default();
 
-            error!(msg = %msg, location = %location, "Panic occurred");
+            crate::error!(msg = %msg, location = %location, "Panic occurred");
         }));
     });
 
@@ -127,4 +130,20 @@ pub enum ConfigError {
 
     #[error("Failed to initialize logging")]
     LoggingInitFailed,
+
+    #[error("Logging has not been initialized")]
+    LoggingNotInitialized,
+
+    #[error("Invalid log filter `{directives}`: {reason}")]
+    InvalidFilter { directives: String, reason: String },
+
+    #[error("Invalid logging config file {path}: {reason}")]
+    ConfigFile { path: String, reason: String },
+
+    #[error("Invalid value `{value}` for `{key}`: {reason}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
 }
@@ -21,7 +21,7 @@ pub fn set_data_dir(path: PathBuf) -> Result<(), FileError> {
         .map_err(|_| FileError::DataDirAlreadySet)
 }
 
-fn app_name() -> &'static str {
+pub(crate) fn app_name() -> &'static str {
     APP_NAME.get().map(|s| s.as_str()).unwrap_or("baad")
 }
 
@@ -44,6 +44,18 @@ pub fn get_data_path(filename: &str) -> Result<PathBuf, FileError> {
     Ok(data_dir.join(filename))
 }
 
+pub fn config_dir() -> Result<&'static Path, FileError> {
+    APP_DIRS
+        .as_ref()
+        .map(|dirs| dirs.config_dir.as_path())
+        .map_err(|_| FileError::AppDirectoryCreationFailed)
+}
+
+pub fn get_config_path(filename: &str) -> Result<PathBuf, FileError> {
+    let config_dir = config_dir()?;
+    Ok(config_dir.join(filename))
+}
+
 pub async fn load_file(path: &Path) -> Result<Vec<u8>, FileError> {
     Ok(fs::read(path).await?)
 }
@@ -1,3 +1,5 @@
+use crate::context::LogContext;
+use crate::redact::Redactor;
 use crate::utils::{contains_url, format_urls, get_level_visual_length, level_to_index};
 
 use chrono::{DateTime, Local};
@@ -18,6 +20,7 @@ const SUCCESS_PREFIX: &str = "[SUCCESS]";
 const CAUSE_PREFIX: &str = "[CAUSE]";
 
 const TIMESTAMP_STYLE: Style = Style::new().bright_black();
+const CONTEXT_STYLE: Style = Style::new().bright_black();
 const ERROR_STYLE: Style = Style::new().red().bold();
 const WARN_STYLE: Style = Style::new().yellow().bold();
 const INFO_STYLE: Style = Style::new().blue().bold();
@@ -42,7 +45,12 @@ pub struct ConsoleFormatter {
 #[derive(Debug, Clone)]
 struct FormatterConfig {
     include_timestamps: bool,
+    include_dates: bool,
     include_spans: bool,
+    stream: Stream,
+    split_streams: bool,
+    redactor: Option<Arc<Redactor>>,
+    fixed_timestamp: Option<String>,
 }
 
 impl Default for ConsoleFormatter {
@@ -56,7 +64,12 @@ impl ConsoleFormatter {
         Self {
             config: Arc::new(FormatterConfig {
                 include_timestamps: false,
+                include_dates: false,
                 include_spans: false,
+                stream: Stream::Stdout,
+                split_streams: false,
+                redactor: None,
+                fixed_timestamp: None,
             }),
         }
     }
@@ -77,20 +90,124 @@ impl ConsoleFormatter {
         self
     }
 
+    /// Writes timestamps as `2024-05-01 14:03:07` rather than just the time of day.
+    pub fn with_dates(mut self, include_dates: bool) -> Self {
+        Arc::make_mut(&mut self.config).include_dates = include_dates;
+        self
+    }
+
     pub fn with_spans(mut self, include_spans: bool) -> Self {
         Arc::make_mut(&mut self.config).include_spans = include_spans;
         self
     }
 
-    fn write_timestamp(&self, writer: &mut Writer<'_>) -> fmt::Result {
+    /// Sets the stream used to detect color support.
+    pub fn with_stream(mut self, stream: Stream) -> Self {
+        Arc::make_mut(&mut self.config).stream = stream;
+        self
+    }
+
+    /// Detects color support against stderr for WARN and ERROR events and against
+    /// stdout for the rest, matching [`crate::config::LogOutput::Split`].
+    pub fn with_split_streams(mut self, split_streams: bool) -> Self {
+        Arc::make_mut(&mut self.config).split_streams = split_streams;
+        self
+    }
+
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        Arc::make_mut(&mut self.config).redactor = Some(redactor);
+        self
+    }
+
+    /// Writes `timestamp` in place of the current time, for reproducible output.
+    pub fn with_fixed_timestamp(mut self, timestamp: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config).fixed_timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Formats `message` as a line logged at `level` outside of any event and
+    /// [`LogContext`], such as a deduplication summary.
+    pub(crate) fn format_message(&self, level: &Level, message: String, ansi: bool) -> String {
+        let mut visitor = FieldCollector::new();
+        visitor.fields.push(("message", Cow::Owned(message)));
+
+        let mut line = String::new();
+        let _ = self.write_event(
+            &mut Writer::new(&mut line),
+            level,
+            visitor,
+            &LogContext::new(),
+            ansi,
+        );
+        line
+    }
+
+    fn write_event(
+        &self,
+        writer: &mut Writer<'_>,
+        level: &Level,
+        mut visitor: FieldCollector,
+        context: &LogContext,
+        ansi: bool,
+    ) -> fmt::Result {
+        if let Some(redactor) = &self.config.redactor {
+            visitor.redact(redactor);
+        }
+
+        let is_success = level == &Level::INFO && visitor.has_success_field();
+        let colors = self.colors(ansi, level);
+
+        if visitor.is_simple_message() && !self.config.include_timestamps {
+            self.write_simple_message(writer, level, is_success, &visitor.fields, context, colors)?;
+            return writeln!(writer);
+        }
+
+        if self.config.include_timestamps {
+            self.write_timestamp(writer, colors)?;
+            write!(writer, " ")?;
+        }
+
+        self.write_level_prefix(writer, level, is_success, colors)?;
+        write!(writer, " ")?;
+        self.write_context(writer, context, colors)?;
+
+        let formatter = FieldFormatter::new(level, is_success, colors);
+        formatter.write_fields(writer, &visitor.fields)?;
+
+        writeln!(writer)?;
+
+        if let Some(cause_value) = visitor.get_cause_value() {
+            self.write_cause_line(writer, cause_value, colors)?;
+        }
+
+        Ok(())
+    }
+
+    fn colors(&self, ansi: bool, level: &Level) -> Colors {
+        let stream = if !self.config.split_streams {
+            self.config.stream
+        } else if *level <= Level::WARN {
+            Stream::Stderr
+        } else {
+            Stream::Stdout
+        };
+
+        Colors { ansi, stream }
+    }
+
+    fn write_timestamp(&self, writer: &mut Writer<'_>, colors: Colors) -> fmt::Result {
+        if let Some(timestamp) = &self.config.fixed_timestamp {
+            return write!(writer, "{}", paint(timestamp, TIMESTAMP_STYLE, colors));
+        }
+
         let now: DateTime<Local> = Local::now();
-        let timestamp = now.format("%H:%M:%S");
+        let timestamp = if self.config.include_dates {
+            now.format("%Y-%m-%d %H:%M:%S")
+        } else {
+            now.format("%H:%M:%S")
+        };
 
-        write!(
-            writer,
-            "{}",
-            timestamp.if_supports_color(Stream::Stdout, |t| t.style(TIMESTAMP_STYLE))
-        )
+        write!(writer, "{}", paint(timestamp, TIMESTAMP_STYLE, colors))
     }
 
     fn write_level_prefix(
@@ -98,6 +215,7 @@ impl ConsoleFormatter {
         writer: &mut Writer<'_>,
         level: &Level,
         is_success: bool,
+        colors: Colors,
     ) -> fmt::Result {
         let visual_length = get_level_visual_length(level, is_success);
         let padding = 9_usize.saturating_sub(visual_length);
@@ -105,20 +223,41 @@ impl ConsoleFormatter {
         write!(writer, "{:width$}", "", width = padding)?;
 
         if is_success {
-            write!(
-                writer,
-                "{}",
-                SUCCESS_PREFIX.if_supports_color(Stream::Stdout, |t| t.style(SUCCESS_STYLE))
-            )
+            write!(writer, "{}", paint(SUCCESS_PREFIX, SUCCESS_STYLE, colors))
         } else {
             let prefix = LEVEL_PREFIXES[level_to_index(level)];
             let style = Self::get_level_style(level);
-            write!(
-                writer,
-                "{}",
-                prefix.if_supports_color(Stream::Stdout, |t| t.style(style))
-            )
+            write!(writer, "{}", paint(prefix, style, colors))
+        }
+    }
+
+    /// Writes the current [`LogContext`] as `[job_id=7 asset=a.png] `.
+    fn write_context(
+        &self,
+        writer: &mut Writer<'_>,
+        context: &LogContext,
+        colors: Colors,
+    ) -> fmt::Result {
+        if context.is_empty() {
+            return Ok(());
+        }
+
+        let mut rendered = String::from("[");
+        for (i, (key, value)) in context.fields().iter().enumerate() {
+            let value = match &self.config.redactor {
+                Some(redactor) => redactor.redact_field(key, Cow::Borrowed(value)),
+                None => Cow::Borrowed(value.as_str()),
+            };
+            if i > 0 {
+                rendered.push(' ');
+            }
+            rendered.push_str(key);
+            rendered.push('=');
+            rendered.push_str(&value);
         }
+        rendered.push(']');
+
+        write!(writer, "{} ", paint(rendered, CONTEXT_STYLE, colors))
     }
 
     fn write_simple_message(
@@ -127,9 +266,12 @@ impl ConsoleFormatter {
         level: &Level,
         is_success: bool,
         fields: &[(&'static str, Cow<'static, str>)],
+        context: &LogContext,
+        colors: Colors,
     ) -> fmt::Result {
-        self.write_level_prefix(writer, level, is_success)?;
+        self.write_level_prefix(writer, level, is_success, colors)?;
         write!(writer, " ")?;
+        self.write_context(writer, context, colors)?;
 
         if let Some((_, message)) = fields.first() {
             write!(writer, "{}", message)?;
@@ -138,9 +280,14 @@ impl ConsoleFormatter {
         Ok(())
     }
 
-    fn write_cause_line(&self, writer: &mut Writer<'_>, cause_value: &str) -> fmt::Result {
+    fn write_cause_line(
+        &self,
+        writer: &mut Writer<'_>,
+        cause_value: &str,
+        colors: Colors,
+    ) -> fmt::Result {
         if self.config.include_timestamps {
-            self.write_timestamp(writer)?;
+            self.write_timestamp(writer, colors)?;
             write!(writer, " ")?;
         }
 
@@ -151,26 +298,15 @@ impl ConsoleFormatter {
             writer,
             "{:width$}{} ",
             "",
-            CAUSE_PREFIX.if_supports_color(Stream::Stdout, |t| t.style(CAUSE_STYLE)),
+            paint(CAUSE_PREFIX, CAUSE_STYLE, colors),
             width = padding
         )?;
 
         if contains_url(cause_value) {
             let formatted = format_urls(
                 cause_value,
-                |text| {
-                    format!(
-                        "{}",
-                        text.if_supports_color(Stream::Stdout, |t| t.style(CAUSE_VALUE_STYLE))
-                    )
-                },
-                |url| {
-                    format!(
-                        "{}",
-                        url.if_supports_color(Stream::Stdout, |t| t
-                            .style(CAUSE_VALUE_STYLE.underline()))
-                    )
-                },
+                |text| format!("{}", paint(text, CAUSE_VALUE_STYLE, colors)),
+                |url| format!("{}", paint(url, CAUSE_VALUE_STYLE.underline(), colors)),
             );
             write!(writer, "{}", formatted)?;
         } else {
@@ -192,50 +328,38 @@ where
         mut writer: Writer<'_>,
         event: &Event<'_>,
     ) -> fmt::Result {
-        let level = event.metadata().level();
         let mut visitor = FieldCollector::new();
         event.record(&mut visitor);
 
-        let is_success = level == &Level::INFO && visitor.has_success_field();
-
-        if visitor.is_simple_message() && !self.config.include_timestamps {
-            self.write_simple_message(&mut writer, level, is_success, &visitor.fields)?;
-            return writeln!(writer);
-        }
-
-        if self.config.include_timestamps {
-            self.write_timestamp(&mut writer)?;
-            write!(writer, " ")?;
-        }
-
-        self.write_level_prefix(&mut writer, level, is_success)?;
-        write!(writer, " ")?;
-
-        let formatter = FieldFormatter::new(level, is_success);
-        formatter.write_fields(&mut writer, &visitor.fields)?;
-
-        writeln!(writer)?;
-
-        if let Some(cause_value) = visitor.get_cause_value() {
-            self.write_cause_line(&mut writer, cause_value)?;
-        }
-
-        Ok(())
+        let ansi = writer.has_ansi_escapes();
+        self.write_event(
+            &mut writer,
+            event.metadata().level(),
+            visitor,
+            &LogContext::current(),
+            ansi,
+        )
     }
 }
 
-struct FieldCollector {
-    fields: SmallVec<[(&'static str, Cow<'static, str>); 4]>,
+pub(crate) struct FieldCollector {
+    pub(crate) fields: SmallVec<[(&'static str, Cow<'static, str>); 4]>,
 }
 
 impl FieldCollector {
     #[inline]
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             fields: SmallVec::new(),
         }
     }
 
+    pub(crate) fn redact(&mut self, redactor: &Redactor) {
+        for (name, value) in &mut self.fields {
+            *value = redactor.redact_field(name, std::mem::take(value));
+        }
+    }
+
     #[inline]
     fn has_success_field(&self) -> bool {
         self.fields
@@ -296,12 +420,17 @@ impl Visit for FieldCollector {
 struct FieldFormatter<'a> {
     level: &'a Level,
     is_success: bool,
+    colors: Colors,
 }
 
 impl<'a> FieldFormatter<'a> {
     #[inline]
-    fn new(level: &'a Level, is_success: bool) -> Self {
-        Self { level, is_success }
+    fn new(level: &'a Level, is_success: bool, colors: Colors) -> Self {
+        Self {
+            level,
+            is_success,
+            colors,
+        }
     }
 
     #[inline]
@@ -359,20 +488,12 @@ impl<'a> FieldFormatter<'a> {
 
     fn write_colored_value(&self, writer: &mut Writer<'_>, value: &str) -> fmt::Result {
         if self.is_success {
-            return write!(
-                writer,
-                "{}",
-                value.if_supports_color(Stream::Stdout, |t| t.style(SUCCESS_VALUE_STYLE))
-            );
+            return write!(writer, "{}", paint(value, SUCCESS_VALUE_STYLE, self.colors));
         }
 
         if !contains_url(value) {
             let style = Self::get_value_style(self.level);
-            write!(
-                writer,
-                "{}",
-                value.if_supports_color(Stream::Stdout, |t| t.style(style))
-            )
+            write!(writer, "{}", paint(value, style, self.colors))
         } else {
             let formatted = self.format_with_urls(value);
             write!(writer, "{}", formatted)
@@ -391,11 +512,7 @@ impl<'a> FieldFormatter<'a> {
             Self::get_value_style(self.level)
         };
 
-        write!(
-            writer,
-            "{}=",
-            field_name.if_supports_color(Stream::Stdout, |t| t.style(style))
-        )?;
+        write!(writer, "{}=", paint(field_name, style, self.colors))?;
         self.write_colored_value(writer, value)?;
         Ok(())
     }
@@ -411,9 +528,42 @@ impl<'a> FieldFormatter<'a> {
     fn format_by_level(&self, value: &str, is_url: bool) -> String {
         let style = Self::get_value_style(self.level);
         let style = if is_url { style.underline() } else { style };
-        format!(
+        format!("{}", paint(value, style, self.colors))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Colors {
+    ansi: bool,
+    stream: Stream,
+}
+
+struct Paint<T> {
+    value: T,
+    style: Style,
+    colors: Colors,
+}
+
+#[inline]
+fn paint<T: fmt::Display>(value: T, style: Style, colors: Colors) -> Paint<T> {
+    Paint {
+        value,
+        style,
+        colors,
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Paint<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.colors.ansi {
+            return self.value.fmt(f);
+        }
+
+        write!(
+            f,
             "{}",
-            value.if_supports_color(Stream::Stdout, |t| t.style(style))
+            self.value
+                .if_supports_color(self.colors.stream, |t| t.style(self.style))
         )
     }
 }
@@ -0,0 +1,177 @@
+use crate::context::LogContext;
+use crate::formatter::FieldCollector;
+use crate::redact::Redactor;
+use crate::syslog::{default_hostname, severity};
+
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, format::Writer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Formats events as GELF 1.1 messages. Event fields and the current [`LogContext`]
+/// become `_`-prefixed additional fields; `message` is sent as `short_message`.
+#[derive(Clone)]
+pub struct GelfFormatter {
+    config: Arc<GelfFormatterConfig>,
+}
+
+#[derive(Debug, Clone)]
+struct GelfFormatterConfig {
+    host: String,
+    static_fields: Vec<(String, Value)>,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl Default for GelfFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GelfFormatter {
+    pub fn new() -> Self {
+        Self {
+            config: Arc::new(GelfFormatterConfig {
+                host: default_hostname(),
+                static_fields: Vec::new(),
+                redactor: None,
+            }),
+        }
+    }
+
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config).host = host.into();
+        self
+    }
+
+    /// Adds `_<key>` to every message, e.g. the app name or environment.
+    pub fn with_static_field(mut self, key: &str, value: impl Into<Value>) -> Self {
+        Arc::make_mut(&mut self.config)
+            .static_fields
+            .push((field_name(key), value.into()));
+        self
+    }
+
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        Arc::make_mut(&mut self.config).redactor = Some(redactor);
+        self
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for GelfFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut visitor = FieldCollector::new();
+        event.record(&mut visitor);
+        if let Some(redactor) = &self.config.redactor {
+            visitor.redact(redactor);
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as f64
+            / 1000.0;
+
+        let short_message = visitor
+            .fields
+            .iter()
+            .find(|(name, _)| *name == "message")
+            .map(|(_, message)| message.to_string())
+            .unwrap_or_default();
+
+        let mut object = Map::new();
+        object.insert("version".to_string(), "1.1".into());
+        object.insert("host".to_string(), self.config.host.as_str().into());
+        object.insert("short_message".to_string(), short_message.into());
+        object.insert("timestamp".to_string(), timestamp.into());
+        object.insert("level".to_string(), severity(metadata.level()).into());
+        object.insert("_target".to_string(), metadata.target().into());
+
+        for (key, value) in &self.config.static_fields {
+            object.insert(key.clone(), value.clone());
+        }
+
+        // Inserted first so that event fields of the same name replace them.
+        for (key, value) in LogContext::current().fields() {
+            let value = match &self.config.redactor {
+                Some(redactor) => redactor.redact_field(key, Cow::Borrowed(value)),
+                None => Cow::Borrowed(value.as_str()),
+            };
+            object.insert(field_name(key), value.as_ref().into());
+        }
+
+        for (name, value) in visitor.fields.iter().filter(|(name, _)| *name != "message") {
+            object.insert(field_name(name), value.as_ref().into());
+        }
+
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Additional field names match `^[\w.\-]*$` and must not be `_id`.
+fn field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    match name.as_str() {
+        "id" => "_id_".to_string(),
+        _ => format!("_{name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactionConfig;
+    use crate::testing::format_events;
+
+    #[test]
+    fn adds_fields_and_context_as_additional_fields() {
+        let redactor = Arc::new(Redactor::new(&RedactionConfig::default()).unwrap());
+        let formatter = GelfFormatter::new()
+            .with_host("db1")
+            .with_static_field("env", "test")
+            .with_redactor(redactor);
+        let output = format_events(formatter, || {
+            LogContext::current()
+                .with("request id", "42")
+                .with("user", "ann")
+                .with("token", "secret")
+                .scope(|| tracing::error!(target: "db", user = "bob", id = 7, "failed"));
+        });
+
+        let message: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(message["version"], "1.1");
+        assert_eq!(message["host"], "db1");
+        assert_eq!(message["short_message"], "failed");
+        assert_eq!(message["level"], 3);
+        assert_eq!(message["_target"], "db");
+        assert_eq!(message["_env"], "test");
+        assert_eq!(message["_user"], "bob");
+        assert_eq!(message["_id_"], "7");
+        assert_eq!(message["_request_id"], "42");
+        assert_eq!(message["_token"], "[REDACTED]");
+    }
+}
@@ -0,0 +1,563 @@
+//! A SQLite database of past events that can be searched across runs.
+//!
+//! ```ignore
+//! let errors = query_history(&HistoryQuery {
+//!     min_level: Some(Level::ERROR),
+//!     fields: vec![("asset".to_string(), "X".to_string())],
+//!     since: Some(Utc::now() - chrono::Duration::days(7)),
+//!     ..HistoryQuery::default()
+//! })?;
+//! ```
+
+use crate::config::HistoryConfig;
+use crate::error::{ConfigError, FileError};
+use crate::memory::LogRecord;
+use crate::redact::Redactor;
+use crate::utils::level_to_index;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, bounded};
+use rusqlite::{Connection, OpenFlags, ToSql, params, params_from_iter};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// File name of the database in [`crate::file::data_dir`] unless configured otherwise.
+pub const DATABASE_NAME: &str = "logs.sqlite";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        app TEXT NOT NULL,
+        pid INTEGER NOT NULL,
+        started_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        run_id INTEGER NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+        timestamp INTEGER NOT NULL,
+        level INTEGER NOT NULL,
+        target TEXT NOT NULL,
+        message TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS event_fields (
+        event_id INTEGER NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        value TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS events_timestamp ON events (timestamp);
+    CREATE INDEX IF NOT EXISTS events_level_timestamp ON events (level, timestamp);
+    CREATE INDEX IF NOT EXISTS events_run_id ON events (run_id);
+    CREATE INDEX IF NOT EXISTS event_fields_event_id ON event_fields (event_id);
+    CREATE INDEX IF NOT EXISTS event_fields_name_value ON event_fields (name, value);
+";
+
+/// How often events older than the retention period are deleted while running.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long a connection waits for another process writing to the same database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Filters for [`query_history`]. Every set field must match; `min_level` keeps
+/// events at that level or more severe, and `target` matches by prefix.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryQuery {
+    pub min_level: Option<Level>,
+    pub target: Option<String>,
+    /// Name and value pairs that must all match a field of the event exactly,
+    /// including fields of its [`LogContext`](crate::context::LogContext).
+    pub fields: Vec<(String, String)>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub contains: Option<String>,
+    pub run_id: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryRecord {
+    /// Identifies the process run that logged the event.
+    pub run_id: u64,
+    pub record: LogRecord,
+}
+
+/// Searches the database in the data directory. See [`query_history_at`].
+pub fn query_history(query: &HistoryQuery) -> Result<Vec<HistoryRecord>, FileError> {
+    query_history_at(&crate::file::get_data_path(DATABASE_NAME)?, query)
+}
+
+/// Returns matching events oldest first, keeping the most recent `limit` of them.
+pub fn query_history_at(
+    path: &Path,
+    query: &HistoryQuery,
+) -> Result<Vec<HistoryRecord>, FileError> {
+    let connection =
+        Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(external)?;
+    connection.busy_timeout(BUSY_TIMEOUT).map_err(external)?;
+
+    let mut conditions: Vec<&str> = Vec::new();
+    let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+    if let Some(level) = query.min_level {
+        conditions.push("e.level <= ?");
+        values.push(Box::new(level_to_index(&level) as i64));
+    }
+    if let Some(target) = &query.target {
+        conditions.push("substr(e.target, 1, length(?)) = ?");
+        values.push(Box::new(target.clone()));
+        values.push(Box::new(target.clone()));
+    }
+    for (name, value) in &query.fields {
+        conditions.push("e.id IN (SELECT event_id FROM event_fields WHERE name = ? AND value = ?)");
+        values.push(Box::new(name.clone()));
+        values.push(Box::new(value.clone()));
+    }
+    if let Some(since) = query.since {
+        conditions.push("e.timestamp >= ?");
+        values.push(Box::new(since.timestamp_micros()));
+    }
+    if let Some(until) = query.until {
+        conditions.push("e.timestamp <= ?");
+        values.push(Box::new(until.timestamp_micros()));
+    }
+    if let Some(needle) = &query.contains {
+        conditions.push(
+            "(instr(e.message, ?) > 0 OR EXISTS (SELECT 1 FROM event_fields f \
+             WHERE f.event_id = e.id AND instr(f.value, ?) > 0))",
+        );
+        values.push(Box::new(needle.clone()));
+        values.push(Box::new(needle.clone()));
+    }
+    if let Some(run_id) = query.run_id {
+        conditions.push("e.run_id = ?");
+        values.push(Box::new(run_id as i64));
+    }
+
+    let mut sql = "SELECT e.id, e.run_id, e.timestamp, e.level, e.target, e.message FROM events e"
+        .to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY e.timestamp DESC, e.id DESC");
+    if let Some(limit) = query.limit {
+        sql.push_str(" LIMIT ?");
+        values.push(Box::new(limit as i64));
+    }
+
+    let mut statement = connection.prepare(&sql).map_err(external)?;
+    let mut records = statement
+        .query_map(params_from_iter(values.iter()), |row| {
+            let id: i64 = row.get(0)?;
+            let run_id: i64 = row.get(1)?;
+            let timestamp: i64 = row.get(2)?;
+            let level: i64 = row.get(3)?;
+            Ok(HistoryRecord {
+                run_id: run_id as u64,
+                record: LogRecord {
+                    id: id as u64,
+                    timestamp: DateTime::from_timestamp_micros(timestamp).unwrap_or_default(),
+                    level: level_from_index(level),
+                    target: row.get(4)?,
+                    message: row.get(5)?,
+                    fields: Vec::new(),
+                },
+            })
+        })
+        .map_err(external)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(external)?;
+
+    let mut fields = connection
+        .prepare("SELECT name, value FROM event_fields WHERE event_id = ?1 ORDER BY rowid")
+        .map_err(external)?;
+    for history in &mut records {
+        history.record.fields = fields
+            .query_map([history.record.id as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(external)?
+            .collect::<Result<_, _>>()
+            .map_err(external)?;
+    }
+
+    records.reverse();
+    Ok(records)
+}
+
+fn external(e: rusqlite::Error) -> FileError {
+    FileError::External(Box::new(e))
+}
+
+fn level_from_index(index: i64) -> Level {
+    match index {
+        0 => Level::ERROR,
+        1 => Level::WARN,
+        2 => Level::INFO,
+        3 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+enum Message {
+    Event(LogRecord),
+    Flush(Sender<()>),
+    Stop,
+}
+
+/// Owns the thread that inserts events into the database for this run.
+pub(crate) struct HistoryWriter {
+    sender: Sender<Message>,
+    min_level: Level,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl HistoryWriter {
+    pub(crate) fn start(config: &HistoryConfig) -> Result<Self, ConfigError> {
+        let path = match &config.path {
+            Some(path) => path.clone(),
+            None => crate::file::get_data_path(DATABASE_NAME)
+                .map_err(|e| ConfigError::External(Box::new(e)))?,
+        };
+        let (connection, run_id) = open_database(&path).map_err(|e| ConfigError::InvalidValue {
+            key: "history.path".to_string(),
+            value: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+
+        let (sender, receiver) = bounded(config.max_buffered_events);
+        let thread_config = config.clone();
+        let handle = thread::Builder::new()
+            .name("baad-history".to_string())
+            .spawn(move || run(connection, run_id, receiver, &thread_config))
+            .map_err(|e| ConfigError::External(Box::new(e)))?;
+
+        Ok(Self {
+            sender,
+            min_level: config.min_level,
+            handle: Some(handle),
+        })
+    }
+
+    pub(crate) fn layer(&self, redactor: Option<&Arc<Redactor>>) -> HistoryLayer {
+        HistoryLayer {
+            sender: self.sender.clone(),
+            min_level: self.min_level,
+            redactor: redactor.cloned(),
+        }
+    }
+
+    /// Blocks until every event queued so far has been inserted.
+    pub(crate) fn flush(&self) {
+        let (done, flushed) = bounded(1);
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            let _ = flushed.recv();
+        }
+    }
+}
+
+impl Drop for HistoryWriter {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Stop);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn open_database(path: &Path) -> rusqlite::Result<(Connection, i64)> {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    let connection = Connection::open(path)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+    connection.pragma_update(None, "synchronous", "NORMAL")?;
+    connection.pragma_update(None, "foreign_keys", true)?;
+    connection.execute_batch(SCHEMA)?;
+
+    connection.execute(
+        "INSERT INTO runs (app, pid, started_at) VALUES (?1, ?2, ?3)",
+        params![
+            crate::file::app_name(),
+            std::process::id(),
+            Utc::now().timestamp_micros()
+        ],
+    )?;
+    let run_id = connection.last_insert_rowid();
+
+    Ok((connection, run_id))
+}
+
+fn run(
+    mut connection: Connection,
+    run_id: i64,
+    receiver: Receiver<Message>,
+    config: &HistoryConfig,
+) {
+    let mut batch = Vec::with_capacity(config.batch_size);
+    let mut deadline = Instant::now() + config.flush_interval;
+    let mut next_prune = Instant::now();
+    let mut reported = false;
+
+    loop {
+        let (flushed, stop) = match receiver.recv_deadline(deadline) {
+            Ok(Message::Event(record)) => {
+                batch.push(record);
+                if batch.len() < config.batch_size {
+                    continue;
+                }
+                (None, false)
+            }
+            Ok(Message::Flush(done)) => (Some(done), false),
+            Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => (None, true),
+            Err(RecvTimeoutError::Timeout) => (None, false),
+        };
+
+        if let Some(retention) = config.retention
+            && Instant::now() >= next_prune
+        {
+            let _ = prune(&connection, run_id, retention);
+            next_prune = Instant::now() + PRUNE_INTERVAL;
+        }
+
+        // The thread cannot log its own failures without feeding them back into the
+        // batch, so only the first one is reported.
+        if let Err(e) = insert(&mut connection, run_id, &batch)
+            && !reported
+        {
+            eprintln!("Failed to write log history: {}", e);
+            reported = true;
+        }
+        batch.clear();
+        deadline = Instant::now() + config.flush_interval;
+
+        if let Some(done) = flushed {
+            let _ = done.send(());
+        }
+        if stop {
+            break;
+        }
+    }
+}
+
+fn insert(connection: &mut Connection, run_id: i64, records: &[LogRecord]) -> rusqlite::Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let transaction = connection.transaction()?;
+    {
+        let mut insert_event = transaction.prepare_cached(
+            "INSERT INTO events (run_id, timestamp, level, target, message) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut insert_field = transaction.prepare_cached(
+            "INSERT INTO event_fields (event_id, name, value) VALUES (?1, ?2, ?3)",
+        )?;
+
+        for record in records {
+            insert_event.execute(params![
+                run_id,
+                record.timestamp.timestamp_micros(),
+                level_to_index(&record.level) as i64,
+                record.target,
+                record.message
+            ])?;
+            let event_id = transaction.last_insert_rowid();
+            for (name, value) in &record.fields {
+                insert_field.execute(params![event_id, name, value])?;
+            }
+        }
+    }
+    transaction.commit()
+}
+
+/// Deletes events older than `retention`, and runs other than this one that have no
+/// events left.
+fn prune(connection: &Connection, run_id: i64, retention: Duration) -> rusqlite::Result<()> {
+    let Some(cutoff) = TimeDelta::from_std(retention)
+        .ok()
+        .and_then(|retention| Utc::now().checked_sub_signed(retention))
+    else {
+        return Ok(());
+    };
+
+    connection.execute(
+        "DELETE FROM events WHERE timestamp < ?1",
+        [cutoff.timestamp_micros()],
+    )?;
+    connection.execute(
+        "DELETE FROM runs WHERE id != ?1 \
+         AND NOT EXISTS (SELECT 1 FROM events WHERE events.run_id = runs.id)",
+        [run_id],
+    )?;
+    Ok(())
+}
+
+pub(crate) struct HistoryLayer {
+    sender: Sender<Message>,
+    min_level: Level,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl<S: Subscriber> Layer<S> for HistoryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() > self.min_level {
+            return;
+        }
+
+        let record = LogRecord::from_event(event, self.redactor.as_deref());
+
+        // Events are dropped rather than blocking the logging thread when the writer
+        // falls behind.
+        let _ = self.sender.try_send(Message::Event(record));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn database(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("baad-history-{}-{name}.sqlite", std::process::id()));
+        remove_database(&path);
+        path
+    }
+
+    fn remove_database(path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    /// Records the events `log` emits as one run and returns its id.
+    fn record_run(path: &Path, log: impl FnOnce()) -> u64 {
+        let config = HistoryConfig {
+            path: Some(path.to_path_buf()),
+            ..HistoryConfig::default()
+        };
+        let writer = HistoryWriter::start(&config).unwrap();
+        let subscriber = tracing_subscriber::registry().with(writer.layer(None));
+        tracing::subscriber::with_default(subscriber, log);
+        writer.flush();
+
+        let runs = query_history_at(path, &HistoryQuery::default()).unwrap();
+        runs.last().map_or(0, |history| history.run_id)
+    }
+
+    fn record(message: &str, timestamp: DateTime<Utc>) -> LogRecord {
+        LogRecord {
+            id: 0,
+            timestamp,
+            level: Level::INFO,
+            target: "app".to_string(),
+            message: message.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn messages(path: &Path, query: HistoryQuery) -> Vec<String> {
+        query_history_at(path, &query)
+            .unwrap()
+            .into_iter()
+            .map(|history| history.record.message)
+            .collect()
+    }
+
+    #[test]
+    fn queries_filter_across_runs() {
+        let path = database("query");
+        let first = record_run(&path, || {
+            tracing::error!(target: "app::download", asset = "X", "download failed");
+            tracing::debug!("not recorded below the minimum level");
+        });
+        let second = record_run(&path, || {
+            tracing::warn!(target: "app::upload", asset = "Y", "upload slow");
+            tracing::error!(target: "app::download", asset = "X", "download failed again");
+        });
+        assert_ne!(first, second);
+
+        assert_eq!(
+            messages(&path, HistoryQuery::default()),
+            ["download failed", "upload slow", "download failed again"]
+        );
+        assert_eq!(
+            messages(
+                &path,
+                HistoryQuery {
+                    min_level: Some(Level::ERROR),
+                    fields: vec![("asset".to_string(), "X".to_string())],
+                    ..HistoryQuery::default()
+                }
+            ),
+            ["download failed", "download failed again"]
+        );
+        assert_eq!(
+            messages(
+                &path,
+                HistoryQuery {
+                    target: Some("app::up".to_string()),
+                    ..HistoryQuery::default()
+                }
+            ),
+            ["upload slow"]
+        );
+        assert_eq!(
+            messages(
+                &path,
+                HistoryQuery {
+                    contains: Some("Y".to_string()),
+                    ..HistoryQuery::default()
+                }
+            ),
+            ["upload slow"]
+        );
+        assert_eq!(
+            messages(
+                &path,
+                HistoryQuery {
+                    run_id: Some(second),
+                    limit: Some(1),
+                    ..HistoryQuery::default()
+                }
+            ),
+            ["download failed again"]
+        );
+
+        let records = query_history_at(&path, &HistoryQuery::default()).unwrap();
+        assert_eq!(records[0].record.level, Level::ERROR);
+        assert_eq!(records[0].record.target, "app::download");
+        assert_eq!(
+            records[0].record.fields,
+            [("asset".to_string(), "X".to_string())]
+        );
+        remove_database(&path);
+    }
+
+    #[test]
+    fn prune_deletes_old_events_and_empty_runs() {
+        let path = database("prune");
+        let (mut connection, old_run) = open_database(&path).unwrap();
+        let old = record("old", Utc::now() - TimeDelta::days(2));
+        insert(&mut connection, old_run, &[old]).unwrap();
+
+        let (mut connection, run_id) = open_database(&path).unwrap();
+        insert(&mut connection, run_id, &[record("new", Utc::now())]).unwrap();
+        prune(&connection, run_id, Duration::from_secs(24 * 60 * 60)).unwrap();
+
+        assert_eq!(messages(&path, HistoryQuery::default()), ["new"]);
+        let runs: i64 = connection
+            .query_row("SELECT COUNT(*) FROM runs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(runs, 1);
+        drop(connection);
+        remove_database(&path);
+    }
+}
@@ -0,0 +1,413 @@
+use crate::context::LogContext;
+use crate::redact::Redactor;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{JsonFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+/// Keys written next to flattened event fields.
+const TOP_LEVEL_KEYS: &[&str] = &["timestamp", "level", "target", "span", "spans", "threadId"];
+
+#[derive(Clone)]
+pub struct JsonFormatter {
+    config: Arc<JsonFormatterConfig>,
+}
+
+#[derive(Debug, Clone)]
+struct JsonFormatterConfig {
+    flatten_fields: bool,
+    include_target: bool,
+    include_thread_ids: bool,
+    include_current_span: bool,
+    include_span_list: bool,
+    key_names: HashMap<String, String>,
+    static_fields: Vec<(String, Value)>,
+    redactor: Option<Arc<Redactor>>,
+    fixed_timestamp: Option<String>,
+}
+
+impl Default for JsonFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonFormatter {
+    pub fn new() -> Self {
+        Self {
+            config: Arc::new(JsonFormatterConfig {
+                flatten_fields: false,
+                include_target: true,
+                include_thread_ids: true,
+                include_current_span: true,
+                include_span_list: true,
+                key_names: HashMap::new(),
+                static_fields: Vec::new(),
+                redactor: None,
+                fixed_timestamp: None,
+            }),
+        }
+    }
+
+    /// Writes event fields at the top level instead of under `fields`. A field that
+    /// would clash with a top-level key such as `level` is written as `fields.level`.
+    pub fn with_flatten_fields(mut self, flatten_fields: bool) -> Self {
+        Arc::make_mut(&mut self.config).flatten_fields = flatten_fields;
+        self
+    }
+
+    pub fn with_target(mut self, include_target: bool) -> Self {
+        Arc::make_mut(&mut self.config).include_target = include_target;
+        self
+    }
+
+    pub fn with_thread_ids(mut self, include_thread_ids: bool) -> Self {
+        Arc::make_mut(&mut self.config).include_thread_ids = include_thread_ids;
+        self
+    }
+
+    pub fn with_current_span(mut self, include_current_span: bool) -> Self {
+        Arc::make_mut(&mut self.config).include_current_span = include_current_span;
+        self
+    }
+
+    pub fn with_span_list(mut self, include_span_list: bool) -> Self {
+        Arc::make_mut(&mut self.config).include_span_list = include_span_list;
+        self
+    }
+
+    /// Renames a top-level key, e.g. `level` to `severity`. With flattened fields this
+    /// also applies to event fields such as `message`.
+    pub fn with_key_name(mut self, key: impl Into<String>, name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config)
+            .key_names
+            .insert(key.into(), name.into());
+        self
+    }
+
+    pub fn with_static_field(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        Arc::make_mut(&mut self.config)
+            .static_fields
+            .push((key.into(), value.into()));
+        self
+    }
+
+    /// Masks event and span field values; static fields are left as configured.
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        Arc::make_mut(&mut self.config).redactor = Some(redactor);
+        self
+    }
+
+    /// Writes `timestamp` in place of the current time, for reproducible output.
+    pub fn with_fixed_timestamp(mut self, timestamp: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config).fixed_timestamp = Some(timestamp.into());
+        self
+    }
+
+    fn key<'a>(&'a self, key: &'a str) -> &'a str {
+        self.config
+            .key_names
+            .get(key)
+            .map(String::as_str)
+            .unwrap_or(key)
+    }
+
+    fn is_top_level_key(&self, key: &str) -> bool {
+        let name = self.key(key);
+        TOP_LEVEL_KEYS
+            .iter()
+            .any(|top_level| self.key(top_level) == name)
+            || self
+                .config
+                .static_fields
+                .iter()
+                .any(|(static_key, _)| self.key(static_key) == name)
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut line = JsonLine::new(self);
+
+        let timestamp = match &self.config.fixed_timestamp {
+            Some(timestamp) => timestamp.clone(),
+            None => Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+        };
+        line.entry("timestamp", timestamp.into());
+        line.entry("level", metadata.level().as_str().into());
+
+        for (key, value) in &self.config.static_fields {
+            line.entry(key, value.clone());
+        }
+
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+        visitor.add_context(&LogContext::current());
+
+        let redactor = self.config.redactor.as_deref();
+        if let Some(redactor) = redactor {
+            for (key, value) in &mut visitor.fields {
+                redactor.redact_json(key, value);
+            }
+        }
+
+        if self.config.flatten_fields {
+            for (key, value) in visitor.fields {
+                if self.is_top_level_key(&key) {
+                    line.entry(&format!("fields.{key}"), value);
+                } else {
+                    line.entry(&key, value);
+                }
+            }
+        } else {
+            let fields = visitor
+                .fields
+                .into_iter()
+                .map(|(key, value)| (key.into_owned(), value));
+            line.entry("fields", Value::Object(fields.collect()));
+        }
+
+        if self.config.include_target {
+            line.entry("target", metadata.target().into());
+        }
+
+        let current_span = event
+            .parent()
+            .and_then(|id| ctx.span(id))
+            .or_else(|| ctx.lookup_current());
+
+        if let Some(span) = &current_span {
+            if self.config.include_current_span {
+                line.entry("span", span_object(span, redactor));
+            }
+
+            if self.config.include_span_list {
+                let spans = span
+                    .scope()
+                    .from_root()
+                    .map(|span| span_object(&span, redactor));
+                line.entry("spans", Value::Array(spans.collect()));
+            }
+        }
+
+        if self.config.include_thread_ids {
+            line.entry(
+                "threadId",
+                format!("{:?}", std::thread::current().id()).into(),
+            );
+        }
+
+        writeln!(writer, "{}", line.finish())
+    }
+}
+
+struct JsonLine<'a> {
+    formatter: &'a JsonFormatter,
+    out: String,
+}
+
+impl<'a> JsonLine<'a> {
+    fn new(formatter: &'a JsonFormatter) -> Self {
+        Self {
+            formatter,
+            out: String::from("{"),
+        }
+    }
+
+    fn entry(&mut self, key: &str, value: Value) {
+        if self.out.len() > 1 {
+            self.out.push(',');
+        }
+
+        self.out
+            .push_str(&Value::from(self.formatter.key(key)).to_string());
+        self.out.push(':');
+        self.out.push_str(&value.to_string());
+    }
+
+    fn finish(mut self) -> String {
+        self.out.push('}');
+        self.out
+    }
+}
+
+fn span_object<S>(span: &SpanRef<'_, S>, redactor: Option<&Redactor>) -> Value
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let mut object = Map::new();
+
+    if let Some(fields) = span.extensions().get::<FormattedFields<JsonFields>>()
+        && let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(fields)
+    {
+        object.extend(fields);
+    }
+
+    if let Some(redactor) = redactor {
+        for (key, value) in &mut object {
+            redactor.redact_json(key, value);
+        }
+    }
+
+    object.insert("name".to_string(), span.name().into());
+    Value::Object(object)
+}
+
+#[derive(Default)]
+struct JsonVisitor {
+    fields: Vec<(Cow<'static, str>, Value)>,
+}
+
+impl JsonVisitor {
+    /// Adds the context's fields after the event's own; event fields win on a clash.
+    fn add_context(&mut self, context: &LogContext) {
+        for (key, value) in context.fields() {
+            if !self.fields.iter().any(|(existing, _)| existing == key) {
+                self.fields
+                    .push((Cow::Owned(key.clone()), value.as_str().into()));
+            }
+        }
+    }
+}
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.push((field.name().into(), value.into()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.push((field.name().into(), value.into()));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.push((field.name().into(), value.into()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.push((field.name().into(), value.into()));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.push((field.name().into(), value.into()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let name = field.name();
+        if name.starts_with("log.") {
+            return;
+        }
+
+        let name = name.strip_prefix("r#").unwrap_or(name);
+        self.fields
+            .push((name.into(), format!("{:?}", value).into()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactionConfig;
+    use crate::redact::REDACTED;
+    use crate::testing::format_events;
+    use serde_json::json;
+
+    fn formatter() -> JsonFormatter {
+        JsonFormatter::new()
+            .with_thread_ids(false)
+            .with_fixed_timestamp("2024-01-01T00:00:00Z")
+    }
+
+    fn parse(output: &str) -> Value {
+        serde_json::from_str(output).unwrap()
+    }
+
+    #[test]
+    fn nests_fields_and_lists_spans() {
+        let output = format_events(formatter(), || {
+            let span = tracing::info_span!("download", url = "https://host/file");
+            span.in_scope(|| {
+                LogContext::current()
+                    .with("request_id", "42")
+                    .with("attempt", "0")
+                    .scope(|| tracing::warn!(target: "net", attempt = 2, done = false, "retrying"));
+            });
+        });
+
+        let span = json!({ "name": "download", "url": "https://host/file" });
+        assert_eq!(
+            parse(&output),
+            json!({
+                "timestamp": "2024-01-01T00:00:00Z",
+                "level": "WARN",
+                "fields": {
+                    "message": "retrying",
+                    "attempt": 2,
+                    "done": false,
+                    "request_id": "42",
+                },
+                "target": "net",
+                "span": span,
+                "spans": [span],
+            })
+        );
+    }
+
+    #[test]
+    fn flattens_renamed_fields_around_top_level_keys() {
+        let formatter = formatter()
+            .with_flatten_fields(true)
+            .with_target(false)
+            .with_key_name("level", "severity")
+            .with_key_name("message", "msg")
+            .with_static_field("service", "worker");
+        let output = format_events(formatter, || {
+            tracing::info!(level = 3, service = "db", "started");
+        });
+
+        assert_eq!(
+            output.trim_end(),
+            r#"{"timestamp":"2024-01-01T00:00:00Z","severity":"INFO","service":"worker","msg":"started","fields.level":3,"fields.service":"db"}"#
+        );
+    }
+
+    #[test]
+    fn redacts_event_context_and_span_fields() {
+        let redactor = Arc::new(Redactor::new(&RedactionConfig::default()).unwrap());
+        let formatter = formatter()
+            .with_span_list(false)
+            .with_static_field("token", "static")
+            .with_redactor(redactor);
+        let output = format_events(formatter, || {
+            let span = tracing::info_span!("login", password = "hunter2");
+            span.in_scope(|| {
+                LogContext::current()
+                    .with("cookie", "abc")
+                    .scope(|| tracing::info!(token = 7, "signed in"));
+            });
+        });
+
+        let line = parse(&output);
+        assert_eq!(line["token"], "static");
+        assert_eq!(line["fields"]["token"], REDACTED);
+        assert_eq!(line["fields"]["cookie"], REDACTED);
+        assert_eq!(line["span"]["password"], REDACTED);
+    }
+}
@@ -1,14 +1,38 @@
 pub mod async_writer;
+mod chrome;
 pub mod config;
+pub mod config_loader;
+pub mod context;
+pub mod dedup;
 pub mod error;
 pub mod file;
 pub mod formatter;
+pub mod gelf;
+#[cfg(feature = "sqlite")]
+pub mod history;
+pub mod json;
+pub mod logfmt;
+pub mod memory;
+pub mod metrics;
+pub mod network;
+#[cfg(feature = "otlp")]
+mod otlp;
+pub mod redact;
+pub mod rolling;
+pub mod session;
+pub mod sink;
+pub mod summary;
+pub mod syslog;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub use error::IntoEyreReport;
 
+mod macros;
 mod utils;
 
-pub use tracing::{debug, error, info, trace, warn};
+#[doc(hidden)]
+pub use tracing as __tracing;
 pub use utils::{run, run_async};
 
 #[cfg(feature = "uniffi")]
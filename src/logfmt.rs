@@ -0,0 +1,171 @@
+use crate::context::LogContext;
+use crate::formatter::FieldCollector;
+use crate::redact::Redactor;
+
+use chrono::{SecondsFormat, Utc};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, format::Writer};
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Debug, Clone, Default)]
+pub struct LogfmtFormatter {
+    include_spans: bool,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl LogfmtFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_spans(mut self, include_spans: bool) -> Self {
+        self.include_spans = include_spans;
+        self
+    }
+
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for LogfmtFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut visitor = FieldCollector::new();
+        event.record(&mut visitor);
+        if let Some(redactor) = &self.redactor {
+            visitor.redact(redactor);
+        }
+
+        write!(
+            writer,
+            "time={} level={} target=",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            metadata.level().as_str().to_ascii_lowercase()
+        )?;
+        write_value(&mut writer, metadata.target())?;
+
+        if self.include_spans
+            && let Some(scope) = ctx.event_scope()
+        {
+            let spans: Vec<&str> = scope.from_root().map(|span| span.name()).collect();
+            write!(writer, " span=")?;
+            write_value(&mut writer, &spans.join(":"))?;
+        }
+
+        if let Some((_, message)) = visitor.fields.iter().find(|(name, _)| *name == "message") {
+            write!(writer, " msg=")?;
+            write_value(&mut writer, message)?;
+        }
+
+        for (name, value) in visitor.fields.iter().filter(|(name, _)| *name != "message") {
+            write!(writer, " {}=", field_key(name))?;
+            write_value(&mut writer, value)?;
+        }
+
+        // Event fields win over context fields of the same name.
+        for (key, value) in LogContext::current().fields() {
+            if !visitor.fields.iter().any(|(name, _)| name == key) {
+                let value = match &self.redactor {
+                    Some(redactor) => redactor.redact_field(key, Cow::Borrowed(value)),
+                    None => Cow::Borrowed(value.as_str()),
+                };
+                write!(writer, " {}=", field_key(key))?;
+                write_value(&mut writer, &value)?;
+            }
+        }
+
+        writeln!(writer)
+    }
+}
+
+/// Keys cannot be quoted, so characters that would end one are replaced with `_`.
+fn field_key(name: &str) -> Cow<'_, str> {
+    let invalid = |c: char| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control();
+    if name.is_empty() {
+        Cow::Borrowed("_")
+    } else if name.contains(invalid) {
+        Cow::Owned(name.replace(invalid, "_"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control())
+}
+
+pub fn write_value(writer: &mut impl fmt::Write, value: &str) -> fmt::Result {
+    if !needs_quotes(value) {
+        return writer.write_str(value);
+    }
+
+    writer.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => writer.write_str("\\\"")?,
+            '\\' => writer.write_str("\\\\")?,
+            '\n' => writer.write_str("\\n")?,
+            '\r' => writer.write_str("\\r")?,
+            '\t' => writer.write_str("\\t")?,
+            c if c.is_control() => write!(writer, "\\u{{{:04x}}}", c as u32)?,
+            c => writer.write_char(c)?,
+        }
+    }
+    writer.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactionConfig;
+    use crate::testing::format_events;
+
+    #[test]
+    fn quotes_values_and_sanitizes_keys() {
+        let output = format_events(LogfmtFormatter::new(), || {
+            LogContext::current()
+                .with("request id", "a=b")
+                .with("user", "ann")
+                .scope(|| tracing::info!(user = "bob", path = "/x y", "saved"));
+        });
+
+        let line = output.trim_end();
+        let fields = &line[line.find(" msg=").unwrap()..];
+        assert_eq!(
+            fields,
+            r#" msg=saved user=bob path="/x y" request_id="a=b""#
+        );
+    }
+
+    #[test]
+    fn redacts_context_values() {
+        let redactor = Arc::new(Redactor::new(&RedactionConfig::default()).unwrap());
+        let output = format_events(LogfmtFormatter::new().with_redactor(redactor), || {
+            LogContext::current()
+                .with("token", "secret")
+                .scope(|| tracing::info!("saved"));
+        });
+
+        assert!(
+            output.trim_end().ends_with(" msg=saved token=[REDACTED]"),
+            "{output}"
+        );
+    }
+}
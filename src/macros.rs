@@ -0,0 +1,94 @@
+//! Logging macros that forward to `tracing`. Under `no_logs` every level is compiled
+//! out, `no_debug` strips `debug!`/`trace!` and `no_error` strips `error!`. Stripped
+//! calls sit behind `if false`, so their arguments are still type-checked and count as
+//! used, but are never evaluated.
+
+#[cfg(not(any(feature = "no_logs", feature = "no_error")))]
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => {
+        $crate::__tracing::error!($($arg)+)
+    };
+}
+
+#[cfg(any(feature = "no_logs", feature = "no_error"))]
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => {
+        if false {
+            $crate::__tracing::error!($($arg)+)
+        }
+    };
+}
+
+#[cfg(not(feature = "no_logs"))]
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        $crate::__tracing::warn!($($arg)+)
+    };
+}
+
+#[cfg(feature = "no_logs")]
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        if false {
+            $crate::__tracing::warn!($($arg)+)
+        }
+    };
+}
+
+#[cfg(not(feature = "no_logs"))]
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => {
+        $crate::__tracing::info!($($arg)+)
+    };
+}
+
+#[cfg(feature = "no_logs")]
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => {
+        if false {
+            $crate::__tracing::info!($($arg)+)
+        }
+    };
+}
+
+#[cfg(not(any(feature = "no_logs", feature = "no_debug")))]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::__tracing::debug!($($arg)+)
+    };
+}
+
+#[cfg(any(feature = "no_logs", feature = "no_debug"))]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        if false {
+            $crate::__tracing::debug!($($arg)+)
+        }
+    };
+}
+
+#[cfg(not(any(feature = "no_logs", feature = "no_debug")))]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        $crate::__tracing::trace!($($arg)+)
+    };
+}
+
+#[cfg(any(feature = "no_logs", feature = "no_debug"))]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        if false {
+            $crate::__tracing::trace!($($arg)+)
+        }
+    };
+}
@@ -0,0 +1,317 @@
+use crate::context::LogContext;
+use crate::formatter::FieldCollector;
+use crate::redact::Redactor;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use once_cell::sync::Lazy;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+static LOG_BUFFER: Lazy<Mutex<LogBuffer>> = Lazy::new(|| Mutex::new(LogBuffer::default()));
+
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    /// The event's fields followed by those of the [`LogContext`] it was logged in.
+    pub fields: Vec<(String, String)>,
+}
+
+impl LogRecord {
+    /// Every output that builds records from events goes through here, so redaction
+    /// applies to the message, the event's fields and the context's fields alike.
+    pub(crate) fn from_event(event: &Event<'_>, redactor: Option<&Redactor>) -> Self {
+        let metadata = event.metadata();
+        let mut visitor = FieldCollector::new();
+        event.record(&mut visitor);
+
+        let mut message = String::new();
+        let mut fields = Vec::with_capacity(visitor.fields.len());
+        for (name, value) in visitor.fields {
+            if name == "message" {
+                message = value.into_owned();
+            } else {
+                fields.push((name.to_string(), value.into_owned()));
+            }
+        }
+
+        // Event fields win over context fields of the same name.
+        for (key, value) in LogContext::current().fields() {
+            if !fields.iter().any(|(name, _)| name == key) {
+                fields.push((key.clone(), value.clone()));
+            }
+        }
+
+        if let Some(redactor) = redactor {
+            message = redactor
+                .redact_field("message", Cow::Owned(message))
+                .into_owned();
+            for (name, value) in &mut fields {
+                *value = redactor
+                    .redact_field(name, Cow::Owned(std::mem::take(value)))
+                    .into_owned();
+            }
+        }
+
+        Self {
+            id: 0,
+            timestamp: Utc::now(),
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message,
+            fields,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{} {:>5} {}: {}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.level,
+            self.target,
+            self.message
+        );
+
+        for (name, value) in &self.fields {
+            line.push_str(&format!(" {name}={value}"));
+        }
+
+        line
+    }
+
+    fn matches(&self, query: &LogQuery) -> bool {
+        if query.after_id.is_some_and(|id| self.id <= id) {
+            return false;
+        }
+        if query.min_level.is_some_and(|level| self.level > level) {
+            return false;
+        }
+        if query
+            .target
+            .as_deref()
+            .is_some_and(|target| !self.target.starts_with(target))
+        {
+            return false;
+        }
+        if query.since.is_some_and(|since| self.timestamp < since) {
+            return false;
+        }
+        if query.until.is_some_and(|until| self.timestamp > until) {
+            return false;
+        }
+        if let Some(needle) = query.contains.as_deref() {
+            return self.message.contains(needle)
+                || self.fields.iter().any(|(_, value)| value.contains(needle));
+        }
+
+        true
+    }
+}
+
+/// Filters for [`query_logs`]. Every set field must match; `min_level` keeps records at
+/// that level or more severe, and `target` matches by prefix.
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+    pub min_level: Option<Level>,
+    pub target: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub contains: Option<String>,
+    pub after_id: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct LogBuffer {
+    capacity: usize,
+    next_id: u64,
+    records: VecDeque<LogRecord>,
+}
+
+impl LogBuffer {
+    fn push(&mut self, mut record: LogRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.next_id += 1;
+        record.id = self.next_id;
+
+        while self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+}
+
+fn lock_buffer() -> MutexGuard<'static, LogBuffer> {
+    LOG_BUFFER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn set_capacity(capacity: usize) {
+    let mut buffer = lock_buffer();
+    buffer.capacity = capacity;
+    while buffer.records.len() > capacity {
+        buffer.records.pop_front();
+    }
+}
+
+/// Returns matching records oldest first, keeping the most recent `limit` of them.
+pub fn query_logs(query: &LogQuery) -> Vec<LogRecord> {
+    let buffer = lock_buffer();
+    let mut records: Vec<LogRecord> = buffer
+        .records
+        .iter()
+        .filter(|record| record.matches(query))
+        .cloned()
+        .collect();
+
+    if let Some(limit) = query.limit
+        && records.len() > limit
+    {
+        records.drain(..records.len() - limit);
+    }
+
+    records
+}
+
+pub fn recent_lines(count: usize) -> Vec<String> {
+    let query = LogQuery {
+        limit: Some(count),
+        ..LogQuery::default()
+    };
+
+    query_logs(&query).iter().map(LogRecord::to_line).collect()
+}
+
+pub fn latest_id() -> Option<u64> {
+    lock_buffer().records.back().map(|record| record.id)
+}
+
+pub fn clear() {
+    lock_buffer().records.clear();
+}
+
+pub struct MemoryLayer {
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl MemoryLayer {
+    pub fn new(redactor: Option<Arc<Redactor>>) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for MemoryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Recording may format values that log themselves, so no lock is held here.
+        let record = LogRecord::from_event(event, self.redactor.as_deref());
+        lock_buffer().push(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn record(level: Level, target: &str, message: &str) -> LogRecord {
+        LogRecord {
+            id: 0,
+            timestamp: Utc::now(),
+            level,
+            target: target.to_string(),
+            message: message.to_string(),
+            fields: vec![("path".to_string(), "/tmp/a".to_string())],
+        }
+    }
+
+    #[test]
+    fn buffer_evicts_oldest_records_and_keeps_counting_ids() {
+        let mut buffer = LogBuffer {
+            capacity: 2,
+            ..LogBuffer::default()
+        };
+        for message in ["a", "b", "c"] {
+            buffer.push(record(Level::INFO, "app", message));
+        }
+
+        let kept: Vec<_> = buffer
+            .records
+            .iter()
+            .map(|record| (record.id, record.message.as_str()))
+            .collect();
+        assert_eq!(kept, [(2, "b"), (3, "c")]);
+    }
+
+    #[test]
+    fn queries_match_every_set_filter() {
+        let mut warn = record(Level::WARN, "app::net", "timed out");
+        warn.id = 5;
+        let matches = |query: LogQuery| warn.matches(&query);
+
+        assert!(matches(LogQuery::default()));
+        assert!(matches(LogQuery {
+            min_level: Some(Level::WARN),
+            target: Some("app".to_string()),
+            contains: Some("/tmp".to_string()),
+            after_id: Some(4),
+            ..LogQuery::default()
+        }));
+        assert!(!matches(LogQuery {
+            min_level: Some(Level::ERROR),
+            ..LogQuery::default()
+        }));
+        assert!(!matches(LogQuery {
+            target: Some("net".to_string()),
+            ..LogQuery::default()
+        }));
+        assert!(!matches(LogQuery {
+            after_id: Some(5),
+            ..LogQuery::default()
+        }));
+        assert!(!matches(LogQuery {
+            until: Some(warn.timestamp - chrono::Duration::seconds(1)),
+            ..LogQuery::default()
+        }));
+    }
+
+    #[test]
+    fn layer_records_redacted_events_for_queries() {
+        set_capacity(100);
+        let config = crate::config::RedactionConfig::default();
+        let redactor = Arc::new(Redactor::new(&config).unwrap());
+        let subscriber = tracing_subscriber::registry().with(MemoryLayer::new(Some(redactor)));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "memory_test", "first");
+            tracing::warn!(target: "memory_test", token = "abc", "second");
+            tracing::error!(target: "memory_test", "third");
+        });
+
+        let query = LogQuery {
+            min_level: Some(Level::WARN),
+            target: Some("memory_test".to_string()),
+            limit: Some(1),
+            ..LogQuery::default()
+        };
+        let records = query_logs(&query);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].message, "third");
+
+        let query = LogQuery {
+            contains: Some("second".to_string()),
+            ..LogQuery::default()
+        };
+        let records = query_logs(&query);
+        assert_eq!(
+            records[0].fields,
+            [("token".to_string(), "[REDACTED]".to_string())]
+        );
+    }
+}
@@ -0,0 +1,461 @@
+use crate::config::MetricsConfig;
+use crate::error::ConfigError;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError, bounded};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Prometheus' default histogram buckets.
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(|| Mutex::new(Registry::default()));
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(f64),
+    Gauge(f64),
+    Histogram(Histogram),
+}
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: Vec<f64>,
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.retain(|bound| bound.is_finite());
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+
+        Self {
+            counts: vec![0; bounds.len()],
+            bounds,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&mut self.counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    metrics: BTreeMap<String, Metric>,
+}
+
+fn lock_registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Adds `value` to a counter, creating it at zero first. Negative values are ignored
+/// since counters only go up.
+pub fn increment_counter(name: &str, value: f64) {
+    if value < 0.0 || !value.is_finite() {
+        return;
+    }
+
+    let mut registry = lock_registry();
+    if let Metric::Counter(total) = registry
+        .metrics
+        .entry(metric_name(name))
+        .or_insert(Metric::Counter(0.0))
+    {
+        *total += value;
+    }
+}
+
+pub fn set_gauge(name: &str, value: f64) {
+    let mut registry = lock_registry();
+    if let Metric::Gauge(current) = registry
+        .metrics
+        .entry(metric_name(name))
+        .or_insert(Metric::Gauge(0.0))
+    {
+        *current = value;
+    }
+}
+
+pub fn add_gauge(name: &str, value: f64) {
+    let mut registry = lock_registry();
+    if let Metric::Gauge(current) = registry
+        .metrics
+        .entry(metric_name(name))
+        .or_insert(Metric::Gauge(0.0))
+    {
+        *current += value;
+    }
+}
+
+/// Creates a histogram with the given upper bounds. Histograms created implicitly by
+/// [`observe_histogram`] use [`DEFAULT_BUCKETS`].
+pub fn register_histogram(name: &str, buckets: &[f64]) {
+    lock_registry().metrics.insert(
+        metric_name(name),
+        Metric::Histogram(Histogram::new(buckets)),
+    );
+}
+
+pub fn observe_histogram(name: &str, value: f64) {
+    let mut registry = lock_registry();
+    if let Metric::Histogram(histogram) = registry
+        .metrics
+        .entry(metric_name(name))
+        .or_insert_with(|| Metric::Histogram(Histogram::new(DEFAULT_BUCKETS)))
+    {
+        histogram.observe(value);
+    }
+}
+
+pub fn reset() {
+    lock_registry().metrics.clear();
+}
+
+/// Renders every metric in the Prometheus text exposition format, with names
+/// prefixed by `<namespace>_` when a namespace is given.
+pub fn render(namespace: Option<&str>) -> String {
+    let registry = lock_registry();
+    let mut out = String::new();
+
+    for (name, metric) in &registry.metrics {
+        let name = match namespace {
+            Some(namespace) => format!("{}_{name}", metric_name(namespace)),
+            None => name.clone(),
+        };
+
+        let _ = match metric {
+            Metric::Counter(value) => write_sample(&mut out, &name, "counter", *value),
+            Metric::Gauge(value) => write_sample(&mut out, &name, "gauge", *value),
+            Metric::Histogram(histogram) => write_histogram(&mut out, &name, histogram),
+        };
+    }
+
+    out
+}
+
+fn write_sample(out: &mut String, name: &str, kind: &str, value: f64) -> fmt::Result {
+    writeln!(out, "# TYPE {name} {kind}")?;
+    writeln!(out, "{name} {}", PromValue(value))
+}
+
+fn write_histogram(out: &mut String, name: &str, histogram: &Histogram) -> fmt::Result {
+    writeln!(out, "# TYPE {name} histogram")?;
+    for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+        writeln!(out, "{name}_bucket{{le=\"{}\"}} {count}", PromValue(*bound))?;
+    }
+    writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", histogram.count)?;
+    writeln!(out, "{name}_sum {}", PromValue(histogram.sum))?;
+    writeln!(out, "{name}_count {}", histogram.count)
+}
+
+struct PromValue(f64);
+
+impl fmt::Display for PromValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            value if value.is_nan() => f.write_str("NaN"),
+            value if value == f64::INFINITY => f.write_str("+Inf"),
+            value if value == f64::NEG_INFINITY => f.write_str("-Inf"),
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Metric names match `[a-zA-Z_:][a-zA-Z0-9_:]*`; anything else becomes `_`.
+fn metric_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Feeds the registry from event fields: `metric.<name>` and `counter.<name>` add to a
+/// counter, `gauge.<name>` sets a gauge and `histogram.<name>` records an observation.
+pub struct MetricsLayer;
+
+impl<S: Subscriber> Layer<S> for MetricsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        event.record(&mut MetricsVisitor);
+    }
+}
+
+struct MetricsVisitor;
+
+impl MetricsVisitor {
+    fn record(&self, field: &Field, value: f64) {
+        let name = field.name();
+        if let Some(name) = name
+            .strip_prefix("metric.")
+            .or_else(|| name.strip_prefix("counter."))
+        {
+            increment_counter(name, value);
+        } else if let Some(name) = name.strip_prefix("gauge.") {
+            set_gauge(name, value);
+        } else if let Some(name) = name.strip_prefix("histogram.") {
+            observe_histogram(name, value);
+        }
+    }
+}
+
+impl Visit for MetricsVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, value as f64);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, value as f64);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if let Ok(value) = value.trim().parse() {
+            self.record(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Writes the textfile every `interval` and serves `/metrics` on the configured
+/// localhost port until dropped. The textfile is written one last time on drop.
+pub(crate) struct MetricsExporter {
+    textfile: Option<PathBuf>,
+    namespace: Option<String>,
+    stop: Option<Sender<()>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl MetricsExporter {
+    pub(crate) fn start(config: &MetricsConfig) -> Result<Self, ConfigError> {
+        let (stop, stopped): (Sender<()>, Receiver<()>) = bounded(0);
+        let mut handles = Vec::new();
+
+        if let Some(path) = config.textfile.clone() {
+            let stopped = stopped.clone();
+            let interval = config.interval;
+            let namespace = config.namespace.clone();
+            handles.push(spawn("baad-metrics-textfile", move || {
+                loop {
+                    let _ = write_textfile(&path, namespace.as_deref());
+                    if stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+                        break;
+                    }
+                }
+                let _ = write_textfile(&path, namespace.as_deref());
+            })?);
+        }
+
+        if let Some(port) = config.port {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+                .map_err(|e| ConfigError::External(Box::new(e)))?;
+            listener
+                .set_nonblocking(true)
+                .map_err(|e| ConfigError::External(Box::new(e)))?;
+            let namespace = config.namespace.clone();
+            handles.push(spawn("baad-metrics-http", move || {
+                while stopped.try_recv() == Err(TryRecvError::Empty) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let _ = serve(stream, namespace.as_deref());
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(ACCEPT_POLL_INTERVAL);
+                        }
+                        Err(_) => thread::sleep(ACCEPT_POLL_INTERVAL),
+                    }
+                }
+            })?);
+        }
+
+        Ok(Self {
+            textfile: config.textfile.clone(),
+            namespace: config.namespace.clone(),
+            stop: Some(stop),
+            handles,
+        })
+    }
+
+    /// Writes the textfile now instead of waiting for the next interval.
+    pub(crate) fn flush(&self) {
+        if let Some(path) = &self.textfile {
+            let _ = write_textfile(path, self.namespace.as_deref());
+        }
+    }
+}
+
+impl Drop for MetricsExporter {
+    fn drop(&mut self) {
+        // Disconnecting the channel tells the threads to stop.
+        self.stop.take();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn spawn<F>(name: &str, f: F) -> Result<JoinHandle<()>, ConfigError>
+where
+    F: FnOnce() + Send + 'static,
+{
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(f)
+        .map_err(|e| ConfigError::External(Box::new(e)))
+}
+
+/// Writes through a temporary file and renames it, so the textfile collector never
+/// reads a partial file.
+fn write_textfile(path: &Path, namespace: Option<&str>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp = path.with_extension("prom.tmp");
+    fs::write(&tmp, render(namespace))?;
+    fs::rename(&tmp, path)
+}
+
+fn serve(mut stream: TcpStream, namespace: Option<&str>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut request = [0u8; 1024];
+    let read = stream.read(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let response = if path == "/metrics" || path == "/" {
+        let body = render(namespace);
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+
+    stream.write_all(response.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// The rendered lines of one metric. The registry is shared by every test, so each
+    /// test uses names of its own instead of resetting it.
+    fn rendered(namespace: Option<&str>, name: &str) -> Vec<String> {
+        render(namespace)
+            .lines()
+            .filter(|line| {
+                line.strip_prefix("# TYPE ")
+                    .unwrap_or(line)
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.starts_with([' ', '_', '{']))
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn renders_counters_and_gauges() {
+        increment_counter("render.downloads", 2.0);
+        increment_counter("render.downloads", 1.5);
+        increment_counter("render.downloads", -4.0);
+        set_gauge("render queue", 10.0);
+        add_gauge("render queue", -3.0);
+
+        assert_eq!(
+            rendered(None, "render_downloads"),
+            ["# TYPE render_downloads counter", "render_downloads 3.5"]
+        );
+        assert_eq!(
+            rendered(Some("app-1"), "app_1_render_queue"),
+            ["# TYPE app_1_render_queue gauge", "app_1_render_queue 7"]
+        );
+    }
+
+    #[test]
+    fn renders_cumulative_histogram_buckets() {
+        register_histogram("histogram_latency", &[1.0, f64::NAN, 0.5, 1.0]);
+        for value in [0.2, 0.7, 3.0] {
+            observe_histogram("histogram_latency", value);
+        }
+
+        assert_eq!(
+            rendered(None, "histogram_latency"),
+            [
+                "# TYPE histogram_latency histogram",
+                "histogram_latency_bucket{le=\"0.5\"} 1",
+                "histogram_latency_bucket{le=\"1\"} 2",
+                "histogram_latency_bucket{le=\"+Inf\"} 3",
+                "histogram_latency_sum 3.9",
+                "histogram_latency_count 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitizes_metric_names() {
+        assert_eq!(metric_name("http.requests-total"), "http_requests_total");
+        assert_eq!(metric_name("5xx"), "_5xx");
+        assert_eq!(metric_name(""), "_");
+    }
+
+    #[test]
+    fn layer_records_metric_fields() {
+        let subscriber = tracing_subscriber::registry().with(MetricsLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(counter.layer_jobs = 1, gauge.layer_workers = 4, "started");
+            tracing::info!(metric.layer_jobs = 2_u64, gauge.layer_workers = "3", "done");
+            tracing::info!(histogram.layer_seconds = 0.01, "timed");
+        });
+
+        assert_eq!(rendered(None, "layer_jobs")[1], "layer_jobs 3");
+        assert_eq!(rendered(None, "layer_workers")[1], "layer_workers 3");
+        assert!(rendered(None, "layer_seconds").contains(&"layer_seconds_count 1".to_string()));
+    }
+}
@@ -0,0 +1,237 @@
+use crate::config::NetworkTransport;
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+const GELF_CHUNK_SIZE: usize = 8192;
+const GELF_CHUNK_HEADER: usize = 12;
+const GELF_MAX_CHUNKS: usize = 128;
+
+static NEXT_GELF_MESSAGE: AtomicU64 = AtomicU64::new(0);
+static DROPPED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Messages every [`NetworkWriter`] has dropped since startup because its endpoint
+/// could not be reached, or because they were too large to send as GELF over UDP.
+pub fn dropped_messages() -> u64 {
+    DROPPED_MESSAGES.load(Ordering::Relaxed)
+}
+
+/// How messages are delimited on stream transports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Protocol {
+    /// RFC 6587 octet counting over TCP.
+    Syslog,
+    /// Null-byte delimited over TCP, chunked over UDP when larger than a datagram.
+    Gelf,
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+}
+
+/// Sends each write as one message to a syslog or GELF endpoint, connecting lazily.
+/// A broken connection is retried once straight away; after that, reconnects back off
+/// from [`MIN_RETRY_DELAY`] to [`MAX_RETRY_DELAY`], and messages written in between
+/// are dropped and counted in [`dropped_messages`].
+pub struct NetworkWriter {
+    transport: NetworkTransport,
+    protocol: Protocol,
+    connection: Option<Connection>,
+    retry_at: Option<Instant>,
+    retry_delay: Duration,
+}
+
+impl NetworkWriter {
+    pub(crate) fn new(transport: NetworkTransport, protocol: Protocol) -> Self {
+        Self {
+            transport,
+            protocol,
+            connection: None,
+            retry_at: None,
+            retry_delay: MIN_RETRY_DELAY,
+        }
+    }
+
+    fn deliver(&mut self, message: &[u8]) -> io::Result<()> {
+        let reconnecting = self.connection.is_none();
+        match self.send(message) {
+            Err(_) if !reconnecting => {
+                // The endpoint may have restarted since the last message.
+                self.connection = None;
+                self.send(message)
+            }
+            result => result,
+        }
+    }
+
+    fn connect(&self) -> io::Result<Connection> {
+        match &self.transport {
+            NetworkTransport::Udp(address) => {
+                let address = resolve(address)?;
+                let local: SocketAddr = if address.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(address)?;
+                Ok(Connection::Udp(socket))
+            }
+            NetworkTransport::Tcp(address) => {
+                let stream = TcpStream::connect_timeout(&resolve(address)?, CONNECT_TIMEOUT)?;
+                stream.set_nodelay(true)?;
+                Ok(Connection::Tcp(stream))
+            }
+            #[cfg(unix)]
+            NetworkTransport::Unix(path) => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(path)?;
+                Ok(Connection::Unix(socket))
+            }
+            #[cfg(not(unix))]
+            NetworkTransport::Unix(_) => Err(io::ErrorKind::Unsupported.into()),
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        if self.connection.is_none() {
+            self.connection = Some(self.connect()?);
+        }
+
+        let protocol = self.protocol;
+        match self.connection.as_mut() {
+            Some(Connection::Udp(socket)) => match protocol {
+                Protocol::Gelf if message.len() > GELF_CHUNK_SIZE => send_chunked(socket, message),
+                _ => socket.send(message).map(|_| ()),
+            },
+            Some(Connection::Tcp(stream)) => match protocol {
+                Protocol::Syslog => {
+                    write!(stream, "{} ", message.len())?;
+                    stream.write_all(message)
+                }
+                Protocol::Gelf => {
+                    stream.write_all(message)?;
+                    stream.write_all(b"\0")
+                }
+            },
+            #[cfg(unix)]
+            Some(Connection::Unix(socket)) => socket.send(message).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for NetworkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let message = buf.strip_suffix(b"\n").unwrap_or(buf);
+        if message.is_empty() {
+            return Ok(buf.len());
+        }
+
+        let now = Instant::now();
+        if self.retry_at.is_some_and(|retry_at| now < retry_at) {
+            DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+            return Ok(buf.len());
+        }
+
+        match self.deliver(message) {
+            Ok(()) => {
+                self.retry_at = None;
+                self.retry_delay = MIN_RETRY_DELAY;
+            }
+            Err(_) => {
+                self.connection = None;
+                self.retry_at = Some(now + self.retry_delay);
+                self.retry_delay = (self.retry_delay * 2).min(MAX_RETRY_DELAY);
+                DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(Connection::Tcp(stream)) = self.connection.as_mut() {
+            stream.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn resolve(address: &str) -> io::Result<SocketAddr> {
+    address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, address.to_string()))
+}
+
+/// Splits a GELF message into chunks of at most [`GELF_CHUNK_SIZE`] bytes, each prefixed
+/// with the chunk magic bytes, a shared message id, its sequence number and the count.
+/// Messages needing more than [`GELF_MAX_CHUNKS`] are dropped, as receivers discard them.
+fn send_chunked(socket: &UdpSocket, message: &[u8]) -> io::Result<()> {
+    let chunks: Vec<&[u8]> = message
+        .chunks(GELF_CHUNK_SIZE - GELF_CHUNK_HEADER)
+        .collect();
+    if chunks.len() > GELF_MAX_CHUNKS {
+        DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+        return Ok(());
+    }
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let id = nanos ^ NEXT_GELF_MESSAGE.fetch_add(1, Ordering::Relaxed);
+
+    let mut datagram = Vec::with_capacity(GELF_CHUNK_SIZE);
+    for (sequence, chunk) in chunks.iter().enumerate() {
+        datagram.clear();
+        datagram.extend_from_slice(&[0x1e, 0x0f]);
+        datagram.extend_from_slice(&id.to_be_bytes());
+        datagram.push(sequence as u8);
+        datagram.push(chunks.len() as u8);
+        datagram.extend_from_slice(chunk);
+        socket.send(&datagram)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_gelf_messages_are_counted_as_dropped() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let address = receiver.local_addr().unwrap().to_string();
+        let mut writer = NetworkWriter::new(NetworkTransport::Udp(address), Protocol::Gelf);
+
+        let dropped = dropped_messages();
+        let oversized = vec![b'x'; GELF_MAX_CHUNKS * GELF_CHUNK_SIZE];
+        writer.write_all(&oversized).unwrap();
+        assert!(dropped_messages() > dropped);
+        assert!(
+            writer.retry_at.is_none(),
+            "an oversized message is not a failure"
+        );
+
+        writer.write_all(b"{\"short_message\":\"fits\"}").unwrap();
+        let mut buf = [0; 1024];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"{\"short_message\":\"fits\"}");
+    }
+}
@@ -0,0 +1,459 @@
+use crate::config::{OtlpConfig, OtlpProtocol};
+use crate::error::ConfigError;
+use crate::memory::LogRecord;
+use crate::redact::Redactor;
+
+use once_cell::sync::Lazy;
+use opentelemetry::KeyValue;
+use opentelemetry::logs::{AnyValue, LogRecord as _, Logger as _, LoggerProvider as _, Severity};
+use opentelemetry::trace::{SpanBuilder, TraceContextExt as _, Tracer, TracerProvider as _};
+use opentelemetry_otlp::{LogExporter, Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{self, BatchLogProcessor, SdkLogger, SdkLoggerProvider};
+use opentelemetry_sdk::trace::{
+    self, BatchSpanProcessor, Sampler, SdkTracer, SdkTracerProvider, SpanData, SpanProcessor,
+};
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Runtime;
+use tracing::dispatcher::{Dispatch, WeakDispatch};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::{FilterFn, filter_fn};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+const SCOPE: &str = "baad_core";
+
+/// Targets of the crates doing the exporting. Exporting their own events would feed
+/// every export back into the next one, and their debug events about each request
+/// would flood every other output.
+const EXPORTER_TARGETS: &[&str] = &[
+    "h2",
+    "hyper",
+    "hyper_util",
+    "opentelemetry",
+    "opentelemetry_otlp",
+    "opentelemetry_sdk",
+    "reqwest",
+    "tonic",
+    "tower",
+];
+
+/// Tracer of the running exporter, used by the span layer from [`span_layer`].
+static TRACER: RwLock<Option<SdkTracer>> = RwLock::new(None);
+/// Builds unsampled spans in the moment between an exporter stopping and the span
+/// layer noticing.
+static IDLE_TRACER: Lazy<SdkTracer> = Lazy::new(|| {
+    SdkTracerProvider::builder()
+        .with_sampler(Sampler::AlwaysOff)
+        .build()
+        .tracer(SCOPE)
+});
+/// The subscriber the span layer was installed in, for looking up span contexts.
+static DISPATCH: OnceLock<WeakDispatch> = OnceLock::new();
+
+fn is_exporting() -> bool {
+    TRACER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Owns the tracer and logger providers, whose batch processors export on their own
+/// threads. gRPC exports also need a tokio runtime, which is kept here so that they
+/// do not depend on the application's.
+pub(crate) struct OtlpExporter {
+    tracer_provider: SdkTracerProvider,
+    logger_provider: SdkLoggerProvider,
+    min_level: Level,
+    runtime: Option<Runtime>,
+}
+
+impl OtlpExporter {
+    pub(crate) fn start(
+        config: &OtlpConfig,
+        redactor: Option<&Arc<Redactor>>,
+    ) -> Result<Self, ConfigError> {
+        let runtime = match config.protocol {
+            OtlpProtocol::Grpc => Some(
+                tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .thread_name("baad-otlp")
+                    .enable_all()
+                    .build()
+                    .map_err(|e| ConfigError::External(Box::new(e)))?,
+            ),
+            OtlpProtocol::HttpProtobuf => None,
+        };
+        let _entered = runtime.as_ref().map(Runtime::enter);
+
+        let (span_exporter, log_exporter) = build_exporters(config)?;
+        let resource = Resource::builder()
+            .with_service_name(
+                config
+                    .service_name
+                    .clone()
+                    .unwrap_or_else(|| crate::file::app_name().to_string()),
+            )
+            .build();
+
+        let span_batch = trace::BatchConfigBuilder::default()
+            .with_max_queue_size(config.max_queue_size)
+            .with_max_export_batch_size(config.max_export_batch_size)
+            .with_scheduled_delay(config.scheduled_delay)
+            .build();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_span_processor(RedactingSpanProcessor {
+                inner: BatchSpanProcessor::builder(span_exporter)
+                    .with_batch_config(span_batch)
+                    .build(),
+                redactor: redactor.cloned(),
+            })
+            .with_resource(resource.clone())
+            .build();
+
+        let log_batch = logs::BatchConfigBuilder::default()
+            .with_max_queue_size(config.max_queue_size)
+            .with_max_export_batch_size(config.max_export_batch_size)
+            .with_scheduled_delay(config.scheduled_delay)
+            .build();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_log_processor(
+                BatchLogProcessor::builder(log_exporter)
+                    .with_batch_config(log_batch)
+                    .build(),
+            )
+            .with_resource(resource)
+            .build();
+
+        *TRACER.write().unwrap_or_else(PoisonError::into_inner) =
+            Some(tracer_provider.tracer(SCOPE));
+
+        Ok(Self {
+            tracer_provider,
+            logger_provider,
+            min_level: config.min_level,
+            runtime,
+        })
+    }
+
+    /// Turns events at `min_level` or above into log records carrying the trace and
+    /// span ids of the span they were logged in. Spans are exported by the layer from
+    /// [`span_layer`].
+    pub(crate) fn log_layer<S>(
+        &self,
+        redactor: Option<&Arc<Redactor>>,
+    ) -> impl Layer<S> + Send + Sync + use<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        OtlpLogLayer {
+            logger: self.logger_provider.logger(SCOPE),
+            min_level: self.min_level,
+            redactor: redactor.cloned(),
+        }
+    }
+
+    /// Exports everything queued so far, waiting up to the export timeout.
+    pub(crate) fn flush(&self) {
+        let _ = self.tracer_provider.force_flush();
+        let _ = self.logger_provider.force_flush();
+    }
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        TRACER
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        // The providers export what is still queued before the runtime goes away.
+        let _ = self.tracer_provider.shutdown();
+        let _ = self.logger_provider.shutdown();
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+fn build_exporters(config: &OtlpConfig) -> Result<(SpanExporter, LogExporter), ConfigError> {
+    let build_error = |e: opentelemetry_otlp::ExporterBuildError| ConfigError::InvalidValue {
+        key: "otlp.endpoint".to_string(),
+        value: config.endpoint.clone().unwrap_or_default(),
+        reason: e.to_string(),
+    };
+
+    match config.protocol {
+        OtlpProtocol::HttpProtobuf => {
+            let endpoint = config
+                .endpoint
+                .as_deref()
+                .unwrap_or("http://127.0.0.1:4318");
+            let endpoint = endpoint.trim_end_matches('/');
+
+            let spans = SpanExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .with_endpoint(format!("{endpoint}/v1/traces"))
+                .with_timeout(config.timeout)
+                .build()
+                .map_err(build_error)?;
+            let logs = LogExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .with_endpoint(format!("{endpoint}/v1/logs"))
+                .with_timeout(config.timeout)
+                .build()
+                .map_err(build_error)?;
+            Ok((spans, logs))
+        }
+        OtlpProtocol::Grpc => {
+            let endpoint = config
+                .endpoint
+                .as_deref()
+                .unwrap_or("http://127.0.0.1:4317");
+
+            let spans = SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .with_timeout(config.timeout)
+                .build()
+                .map_err(build_error)?;
+            let logs = LogExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .with_timeout(config.timeout)
+                .build()
+                .map_err(build_error)?;
+            Ok((spans, logs))
+        }
+    }
+}
+
+fn is_exporter_target(target: &str) -> bool {
+    EXPORTER_TARGETS.iter().any(|exporter| {
+        target
+            .strip_prefix(exporter)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    })
+}
+
+/// Drops spans and events from [`EXPORTER_TARGETS`] for every layer it is combined
+/// with, including [`OtlpExporter::log_layer`].
+pub(crate) fn exporter_target_filter() -> FilterFn<impl Fn(&Metadata<'_>) -> bool> {
+    filter_fn(|metadata| !is_exporter_target(metadata.target()))
+}
+
+/// Turns spans into OTLP spans while an exporter is running. `OpenTelemetrySpanExt`
+/// finds this layer by downcasting the subscriber, which reloadable layers do not pass
+/// through, so it is installed once outside them and follows exporters as they start
+/// and stop.
+pub(crate) fn span_layer<S>() -> impl Layer<S> + Send + Sync
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    SkipExporterTargets(tracing_opentelemetry::layer().with_tracer(ActiveTracer))
+}
+
+/// Builds spans with the running exporter's tracer.
+struct ActiveTracer;
+
+impl Tracer for ActiveTracer {
+    type Span = trace::Span;
+
+    fn build_with_context(
+        &self,
+        builder: SpanBuilder,
+        parent_cx: &opentelemetry::Context,
+    ) -> Self::Span {
+        let tracer = TRACER
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        tracer
+            .as_ref()
+            .unwrap_or(&IDLE_TRACER)
+            .build_with_context(builder, parent_cx)
+    }
+}
+
+/// Passes everything except spans and events from [`EXPORTER_TARGETS`], and spans
+/// started while no exporter is running, to `L`. Spans it never saw created are
+/// ignored by the OpenTelemetry layer.
+struct SkipExporterTargets<L>(L);
+
+impl<S, L> Layer<S> for SkipExporterTargets<L>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    L: Layer<S>,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        let _ = DISPATCH.set(subscriber.downgrade());
+        self.0.on_register_dispatch(subscriber);
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if is_exporting() && !is_exporter_target(attrs.metadata().target()) {
+            self.0.on_new_span(attrs, id, ctx);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.0.on_record(id, values, ctx);
+    }
+
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: Context<'_, S>) {
+        self.0.on_follows_from(id, follows, ctx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if is_exporting() && !is_exporter_target(event.metadata().target()) {
+            self.0.on_event(event, ctx);
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.0.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.0.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.0.on_close(id, ctx);
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        self.0.on_id_change(old, new, ctx);
+    }
+
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            return Some(self as *const Self as *const ());
+        }
+        // SAFETY: forwarded unchanged to the wrapped layer.
+        unsafe { self.0.downcast_raw(id) }
+    }
+}
+
+/// Redacts span attributes and span events before they are queued for export. The
+/// OpenTelemetry layer records span fields and events as they are, and is shared by
+/// every exporter, so its output is redacted here rather than in the layer.
+#[derive(Debug)]
+struct RedactingSpanProcessor<P> {
+    inner: P,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl<P: SpanProcessor> SpanProcessor for RedactingSpanProcessor<P> {
+    fn on_start(&self, span: &mut trace::Span, cx: &opentelemetry::Context) {
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        if let Some(redactor) = &self.redactor {
+            redact_attributes(redactor, &mut span.attributes);
+            for event in &mut span.events.events {
+                // Events from the OpenTelemetry layer are named after their message.
+                if let Cow::Owned(name) = redact_text(redactor, "message", &event.name) {
+                    event.name = Cow::Owned(name);
+                }
+                redact_attributes(redactor, &mut event.attributes);
+            }
+        }
+        self.inner.on_end(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+fn redact_attributes(redactor: &Redactor, attributes: &mut [KeyValue]) {
+    for attribute in attributes {
+        if let Cow::Owned(value) =
+            redact_text(redactor, attribute.key.as_str(), &attribute.value.as_str())
+        {
+            attribute.value = value.into();
+        }
+    }
+}
+
+/// Returns `Cow::Owned` only if redaction changed `value`.
+fn redact_text<'a>(redactor: &Redactor, name: &str, value: &'a str) -> Cow<'a, str> {
+    match redactor.redact_field(name, Cow::Borrowed(value)) {
+        Cow::Borrowed(redacted) if redacted != value => Cow::Owned(redacted.to_string()),
+        redacted => redacted,
+    }
+}
+
+struct OtlpLogLayer {
+    logger: SdkLogger,
+    min_level: Level,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl<S> Layer<S> for OtlpLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > self.min_level {
+            return;
+        }
+
+        let mut record = self.logger.create_log_record();
+        record.set_timestamp(SystemTime::now());
+        record.set_severity_number(severity(metadata.level()));
+        record.set_severity_text(metadata.level().as_str());
+        record.set_target(metadata.target().to_string());
+
+        // Covers events given an explicit parent as well as those in the entered span.
+        if let Some(span) = ctx.event_span(event)
+            && let Some(dispatch) = DISPATCH.get().and_then(WeakDispatch::upgrade)
+            && let Some(cx) = tracing_opentelemetry::get_otel_context(&span.id(), &dispatch)
+        {
+            let span = cx.span();
+            let span_context = span.span_context();
+            if span_context.is_valid() {
+                record.set_trace_context(
+                    span_context.trace_id(),
+                    span_context.span_id(),
+                    Some(span_context.trace_flags()),
+                );
+            }
+        }
+
+        let logged = LogRecord::from_event(event, self.redactor.as_deref());
+        record.set_body(AnyValue::from(logged.message));
+        for (name, value) in logged.fields {
+            record.add_attribute(name, value);
+        }
+
+        self.logger.emit(record);
+    }
+}
+
+fn severity(level: &Level) -> Severity {
+    match *level {
+        Level::ERROR => Severity::Error,
+        Level::WARN => Severity::Warn,
+        Level::INFO => Severity::Info,
+        Level::DEBUG => Severity::Debug,
+        Level::TRACE => Severity::Trace,
+    }
+}
@@ -0,0 +1,230 @@
+use crate::config::RedactionConfig;
+use crate::error::ConfigError;
+use crate::utils::{contains_url, format_urls};
+
+use lazy_regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Masks secrets before they reach an output: values of sensitive fields, URL query
+/// parameter values, and matches of user-supplied patterns.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    fields: Vec<String>,
+    query_params: bool,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Result<Self, ConfigError> {
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| ConfigError::InvalidValue {
+                    key: "redaction.patterns".to_string(),
+                    value: pattern.clone(),
+                    reason: e.to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            fields: config
+                .fields
+                .iter()
+                .map(|f| f.to_ascii_lowercase())
+                .collect(),
+            query_params: config.query_params,
+            patterns,
+        })
+    }
+
+    /// Field names match case-insensitively, either whole or as the last
+    /// `.`-separated segment (`http.authorization`).
+    pub fn is_sensitive_field(&self, name: &str) -> bool {
+        let last = name.rsplit('.').next().unwrap_or(name);
+        self.fields
+            .iter()
+            .any(|field| name.eq_ignore_ascii_case(field) || last.eq_ignore_ascii_case(field))
+    }
+
+    pub fn redact_field<'a>(&self, name: &str, value: Cow<'a, str>) -> Cow<'a, str> {
+        if self.is_sensitive_field(name) {
+            return Cow::Borrowed(REDACTED);
+        }
+
+        let redacted = match self.redact(&value) {
+            Cow::Owned(redacted) => Some(redacted),
+            Cow::Borrowed(_) => None,
+        };
+        redacted.map_or(value, Cow::Owned)
+    }
+
+    /// Like [`Redactor::redact_field`], masking a sensitive field whatever its type.
+    pub fn redact_json(&self, name: &str, value: &mut Value) {
+        if self.is_sensitive_field(name) {
+            *value = REDACTED.into();
+        } else if let Value::String(text) = value
+            && let Cow::Owned(redacted) = self.redact(text)
+        {
+            *text = redacted;
+        }
+    }
+
+    /// Redacts URL query values and pattern matches in free text such as messages.
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+
+        if self.query_params && contains_url(&text) {
+            let redacted = format_urls(&text, str::to_string, redact_query);
+            if redacted != *text {
+                text = Cow::Owned(redacted);
+            }
+        }
+
+        for pattern in &self.patterns {
+            if let Some(redacted) = redact_pattern(pattern, &text) {
+                text = Cow::Owned(redacted);
+            }
+        }
+
+        text
+    }
+}
+
+/// Replaces every capture group of `pattern`, or the whole match if it has none.
+fn redact_pattern(pattern: &Regex, text: &str) -> Option<String> {
+    if !pattern.is_match(text) {
+        return None;
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut last_end = 0;
+
+    for captures in pattern.captures_iter(text) {
+        let ranges: Vec<_> = if captures.len() > 1 {
+            captures
+                .iter()
+                .skip(1)
+                .flatten()
+                .map(|m| m.range())
+                .collect()
+        } else {
+            captures.get(0).map(|m| m.range()).into_iter().collect()
+        };
+
+        for range in ranges {
+            if range.start < last_end {
+                continue;
+            }
+            result.push_str(&text[last_end..range.start]);
+            result.push_str(REDACTED);
+            last_end = range.end;
+        }
+    }
+
+    result.push_str(&text[last_end..]);
+    Some(result)
+}
+
+/// `https://host/path?sig=abc&x=1#top` becomes
+/// `https://host/path?sig=[REDACTED]&x=[REDACTED]#top`.
+fn redact_query(url: &str) -> String {
+    let Some((base, rest)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let (query, fragment) = match rest.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (rest, None),
+    };
+
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) => format!("{key}={REDACTED}"),
+            None => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    match fragment {
+        Some(fragment) => format!("{base}?{query}#{fragment}"),
+        None => format!("{base}?{query}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(patterns: &[&str]) -> Redactor {
+        let config = RedactionConfig {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            ..RedactionConfig::default()
+        };
+        Redactor::new(&config).unwrap()
+    }
+
+    #[test]
+    fn masks_sensitive_fields_by_name_or_last_segment() {
+        let redactor = redactor(&[]);
+        for name in ["password", "Token", "http.authorization"] {
+            assert_eq!(
+                redactor.redact_field(name, "hunter2".into()),
+                REDACTED,
+                "{name}"
+            );
+        }
+        assert_eq!(redactor.redact_field("tokens", "42".into()), "42");
+        assert_eq!(
+            redactor.redact_field("authorization.kind", "basic".into()),
+            "basic"
+        );
+    }
+
+    #[test]
+    fn masks_sensitive_json_values_of_any_type() {
+        let redactor = redactor(&[]);
+        let mut value = serde_json::json!({ "id": 7 });
+        redactor.redact_json("cookie", &mut value);
+        assert_eq!(value, REDACTED);
+
+        let mut value = Value::from(7);
+        redactor.redact_json("id", &mut value);
+        assert_eq!(value, 7);
+    }
+
+    #[test]
+    fn masks_url_query_values() {
+        let redactor = redactor(&[]);
+        assert_eq!(
+            redactor.redact("GET https://host/path?sig=abc&flag#top failed"),
+            "GET https://host/path?sig=[REDACTED]&flag#top failed"
+        );
+        assert!(matches!(redactor.redact("no urls here"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn masks_capture_groups_or_whole_matches() {
+        let redactor = redactor(&[r"key-(\w+)", r"\d{4}-\d{4}"]);
+        assert_eq!(
+            redactor.redact("key-abc and key-def, card 1234-5678"),
+            "key-[REDACTED] and key-[REDACTED], card [REDACTED]"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let config = RedactionConfig {
+            patterns: vec!["(".to_string()],
+            ..RedactionConfig::default()
+        };
+        assert!(matches!(
+            Redactor::new(&config),
+            Err(ConfigError::InvalidValue { key, .. }) if key == "redaction.patterns"
+        ));
+    }
+}
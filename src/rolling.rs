@@ -0,0 +1,231 @@
+use crate::config::{FileLogConfig, LogRotation};
+use crate::error::ConfigError;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+const LOG_SUFFIX: &str = "log";
+const MIN_SIZE_ROLLING_FILES: usize = 2;
+
+pub fn log_dir(config: &FileLogConfig) -> Result<PathBuf, ConfigError> {
+    match &config.directory {
+        Some(dir) => Ok(dir.clone()),
+        None => crate::file::get_data_path("logs").map_err(|e| ConfigError::External(Box::new(e))),
+    }
+}
+
+pub fn open_log_writer(config: &FileLogConfig) -> Result<Box<dyn Write + Send>, ConfigError> {
+    let directory = log_dir(config)?;
+    fs::create_dir_all(&directory).map_err(|e| ConfigError::External(Box::new(e)))?;
+
+    let (min_files, reason) = match config.rotation {
+        LogRotation::Size { .. } => (
+            MIN_SIZE_ROLLING_FILES,
+            "size rotation needs room for the current file and a rotated one",
+        ),
+        LogRotation::Daily | LogRotation::Hourly => (1, "must keep at least the current file"),
+    };
+    if config.max_files < min_files {
+        return Err(ConfigError::InvalidValue {
+            key: "file.max_files".to_string(),
+            value: config.max_files.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+    let max_files = config.max_files;
+
+    let writer: Box<dyn Write + Send> = match config.rotation {
+        LogRotation::Daily => Box::new(time_rolling(
+            &directory,
+            config,
+            Rotation::DAILY,
+            max_files,
+        )?),
+        LogRotation::Hourly => Box::new(time_rolling(
+            &directory,
+            config,
+            Rotation::HOURLY,
+            max_files,
+        )?),
+        LogRotation::Size { max_bytes } => Box::new(
+            SizeRollingWriter::new(directory, &config.file_name_prefix, max_bytes, max_files)
+                .map_err(|e| ConfigError::External(Box::new(e)))?,
+        ),
+    };
+
+    Ok(writer)
+}
+
+fn time_rolling(
+    directory: &Path,
+    config: &FileLogConfig,
+    rotation: Rotation,
+    max_files: usize,
+) -> Result<RollingFileAppender, ConfigError> {
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&config.file_name_prefix)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(max_files)
+        .build(directory)
+        .map_err(|e| ConfigError::External(Box::new(e)))
+}
+
+/// Appends to `<prefix>.log` and shifts it to `<prefix>.1.log`, `<prefix>.2.log`, ...
+/// once it would grow past `max_bytes`, keeping at most `max_files` files in total.
+/// `max_files` must be at least 2, otherwise rotating would only truncate the file;
+/// [`open_log_writer`] checks this before constructing one.
+pub(crate) struct SizeRollingWriter {
+    directory: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRollingWriter {
+    pub(crate) fn new(
+        directory: PathBuf,
+        prefix: &str,
+        max_bytes: u64,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let path = directory.join(format!("{prefix}.{LOG_SUFFIX}"));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            directory,
+            prefix: prefix.to_string(),
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn path_for(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.directory.join(format!("{}.{LOG_SUFFIX}", self.prefix))
+        } else {
+            self.directory
+                .join(format!("{}.{index}.{LOG_SUFFIX}", self.prefix))
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let oldest = self.max_files - 1;
+        let _ = fs::remove_file(self.path_for(oldest));
+        for index in (0..oldest).rev() {
+            let from = self.path_for(index);
+            if from.exists() {
+                fs::rename(&from, self.path_for(index + 1))?;
+            }
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(self.path_for(0))?;
+        self.written = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for SizeRollingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("baad-rolling-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn size_rotation_shifts_files_and_keeps_max_files() {
+        let directory = temp_dir("size");
+        let config = FileLogConfig {
+            directory: Some(directory.clone()),
+            file_name_prefix: "app".to_string(),
+            rotation: LogRotation::Size { max_bytes: 10 },
+            max_files: 3,
+        };
+
+        let mut writer = open_log_writer(&config).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let read = |name: &str| fs::read_to_string(directory.join(name)).unwrap();
+        assert_eq!(read("app.log"), "fourth\n");
+        assert_eq!(read("app.1.log"), "third\n");
+        assert_eq!(read("app.2.log"), "second\n");
+        assert!(!directory.join("app.3.log").exists());
+
+        drop(writer);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn reopening_appends_to_the_current_file() {
+        let directory = temp_dir("append");
+        for line in ["one\n", "two\n"] {
+            let mut writer = SizeRollingWriter::new(directory.clone(), "app", 1024, 2).unwrap();
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+
+        let contents = fs::read_to_string(directory.join("app.log")).unwrap();
+        assert_eq!(contents, "one\ntwo\n");
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn rejects_too_few_files_for_the_rotation() {
+        let directory = temp_dir("reject");
+        let config = |rotation, max_files| FileLogConfig {
+            directory: Some(directory.clone()),
+            rotation,
+            max_files,
+            ..FileLogConfig::default()
+        };
+
+        for (rotation, max_files) in [
+            (LogRotation::Size { max_bytes: 10 }, 1),
+            (LogRotation::Daily, 0),
+            (LogRotation::Hourly, 0),
+        ] {
+            assert!(matches!(
+                open_log_writer(&config(rotation, max_files)),
+                Err(ConfigError::InvalidValue { key, .. }) if key == "file.max_files"
+            ));
+        }
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::async_writer::SharedMakeWriter;
+use crate::config::SessionConfig;
+use crate::error::ConfigError;
+use crate::summary::LogCounts;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use once_cell::sync::Lazy;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use tracing_subscriber::fmt::MakeWriter;
+
+const LOG_SUFFIX: &str = "log";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+static SESSION_ID: Lazy<String> = Lazy::new(|| {
+    format!(
+        "{}-{}",
+        Utc::now().format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    )
+});
+
+static SESSION_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Identifies this process run. Stays the same if logging is shut down and initialized
+/// again.
+pub fn session_id() -> &'static str {
+    &SESSION_ID
+}
+
+/// The file the active session is being written to, if session logging is enabled.
+pub fn session_file() -> Option<PathBuf> {
+    SESSION_FILE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+pub fn session_dir(config: &SessionConfig) -> Result<PathBuf, ConfigError> {
+    match &config.directory {
+        Some(dir) => Ok(dir.clone()),
+        None => {
+            crate::file::get_data_path("sessions").map_err(|e| ConfigError::External(Box::new(e)))
+        }
+    }
+}
+
+/// Creates the session directory, prunes old sessions and returns the path for this
+/// run's log file.
+pub(crate) fn prepare(config: &SessionConfig) -> Result<PathBuf, ConfigError> {
+    let directory = session_dir(config)?;
+    fs::create_dir_all(&directory).map_err(|e| ConfigError::External(Box::new(e)))?;
+
+    let path = directory.join(format!(
+        "{}-{}.{LOG_SUFFIX}",
+        config.file_name_prefix,
+        session_id()
+    ));
+
+    prune_sessions(&directory, config, &path).map_err(|e| ConfigError::External(Box::new(e)))?;
+    Ok(path)
+}
+
+/// Removes session files older than `max_age_days` and all but the newest
+/// `max_sessions - 1` others, leaving room for the current one.
+fn prune_sessions(directory: &Path, config: &SessionConfig, current: &Path) -> io::Result<()> {
+    let prefix = format!("{}-", config.file_name_prefix);
+    let suffix = format!(".{LOG_SUFFIX}");
+
+    let mut sessions: Vec<(SystemTime, PathBuf)> = fs::read_dir(directory)?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(&suffix))
+        })
+        .filter(|entry| entry.path() != current)
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+
+    sessions.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    let keep = config.max_sessions.max(1) - 1;
+    let max_age = config
+        .max_age_days
+        .map(|days| Duration::from_secs(days * SECONDS_PER_DAY));
+    let now = SystemTime::now();
+
+    for (index, (modified, path)) in sessions.iter().enumerate() {
+        let expired = max_age
+            .is_some_and(|max_age| now.duration_since(*modified).unwrap_or_default() > max_age);
+
+        if index >= keep || expired {
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A running session: writes the header when started and the footer, with the
+/// duration and warning/error counts, when finished.
+pub(crate) struct Session {
+    writer: SharedMakeWriter,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    counts_at_start: LogCounts,
+}
+
+impl Session {
+    pub(crate) fn start(path: PathBuf, writer: SharedMakeWriter) -> Self {
+        let session = Self {
+            writer,
+            started_at: Utc::now(),
+            started: Instant::now(),
+            counts_at_start: crate::summary::log_counts(),
+        };
+
+        session.write(&header(session.started_at));
+        *SESSION_FILE.lock().unwrap_or_else(PoisonError::into_inner) = Some(path);
+        session
+    }
+
+    pub(crate) fn finish(&self) {
+        let counts = crate::summary::log_counts().since(&self.counts_at_start);
+        let footer = format!(
+            "==== session {} ended at {}: duration {:.3}s, {} errors, {} warnings ====\n",
+            session_id(),
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.started.elapsed().as_secs_f64(),
+            counts.error,
+            counts.warn,
+        );
+
+        self.write(&footer);
+        *SESSION_FILE.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    fn write(&self, text: &str) {
+        let _ = self.writer.make_writer().write_all(text.as_bytes());
+    }
+}
+
+fn header(started_at: DateTime<Utc>) -> String {
+    let data_dir = crate::file::data_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    let features = match enabled_features().as_slice() {
+        [] => "none".to_string(),
+        features => features.join(", "),
+    };
+
+    let mut header = String::new();
+    let _ = writeln!(
+        header,
+        "==== session {} started at {} ====",
+        session_id(),
+        started_at.to_rfc3339_opts(SecondsFormat::Millis, true)
+    );
+    let _ = writeln!(header, "app: {}", crate::file::app_name());
+    let _ = writeln!(header, "version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(header, "os: {}", std::env::consts::OS);
+    let _ = writeln!(header, "arch: {}", std::env::consts::ARCH);
+    let _ = writeln!(header, "features: {features}");
+    let _ = writeln!(header, "data_dir: {data_dir}");
+    header.push_str("====\n");
+    header
+}
+
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("uniffi", cfg!(feature = "uniffi")),
+        ("no_logs", cfg!(feature = "no_logs")),
+        ("no_debug", cfg!(feature = "no_debug")),
+        ("no_error", cfg!(feature = "no_error")),
+        ("otlp", cfg!(feature = "otlp")),
+        ("sqlite", cfg!(feature = "sqlite")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
@@ -0,0 +1,112 @@
+use crate::memory::LogRecord;
+use crate::redact::Redactor;
+
+use once_cell::sync::Lazy;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+pub trait LogSink: Send + Sync {
+    fn on_log(&self, record: LogRecord);
+}
+
+struct RegisteredSink {
+    id: u64,
+    min_level: Level,
+    sink: Arc<dyn LogSink>,
+}
+
+static SINKS: Lazy<RwLock<Vec<RegisteredSink>>> = Lazy::new(|| RwLock::new(Vec::new()));
+static NEXT_SINK_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_RECORD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static IN_SINK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Forwards every event at `min_level` or more severe to `sink`. Events logged from
+/// inside a sink are not forwarded again.
+pub fn register_log_sink(sink: Arc<dyn LogSink>, min_level: Level) -> u64 {
+    let id = NEXT_SINK_ID.fetch_add(1, Ordering::Relaxed);
+    SINKS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(RegisteredSink {
+            id,
+            min_level,
+            sink,
+        });
+    id
+}
+
+pub fn unregister_log_sink(id: u64) -> bool {
+    let mut sinks = SINKS.write().unwrap_or_else(PoisonError::into_inner);
+    let len = sinks.len();
+    sinks.retain(|sink| sink.id != id);
+    sinks.len() != len
+}
+
+pub fn clear_log_sinks() {
+    SINKS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+pub struct SinkLayer {
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl SinkLayer {
+    pub fn new(redactor: Option<Arc<Redactor>>) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if IN_SINK.with(Cell::get) {
+            return;
+        }
+
+        let level = *event.metadata().level();
+        let sinks: Vec<Arc<dyn LogSink>> = {
+            let sinks = SINKS.read().unwrap_or_else(PoisonError::into_inner);
+            sinks
+                .iter()
+                .filter(|sink| level <= sink.min_level)
+                .map(|sink| sink.sink.clone())
+                .collect()
+        };
+
+        if sinks.is_empty() {
+            return;
+        }
+
+        let mut record = LogRecord::from_event(event, self.redactor.as_deref());
+        record.id = NEXT_RECORD_ID.fetch_add(1, Ordering::Relaxed);
+
+        let _guard = InSinkGuard::enter();
+        for sink in sinks {
+            sink.on_log(record.clone());
+        }
+    }
+}
+
+/// Marks the thread as inside a sink until dropped, including when a sink panics.
+struct InSinkGuard;
+
+impl InSinkGuard {
+    fn enter() -> Self {
+        IN_SINK.with(|in_sink| in_sink.set(true));
+        Self
+    }
+}
+
+impl Drop for InSinkGuard {
+    fn drop(&mut self) {
+        IN_SINK.with(|in_sink| in_sink.set(false));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::memory::LogRecord;
+use crate::redact::Redactor;
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// How many ERROR and how many WARN messages are kept for the summary.
+const MAX_MESSAGES_PER_LEVEL: usize = 5;
+
+static COUNTS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+static MESSAGES: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+/// Number of events emitted at each level since startup or the last [`reset`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogCounts {
+    pub error: u64,
+    pub warn: u64,
+    pub info: u64,
+    pub debug: u64,
+    pub trace: u64,
+}
+
+impl LogCounts {
+    pub(crate) fn since(&self, earlier: &LogCounts) -> LogCounts {
+        LogCounts {
+            error: self.error.saturating_sub(earlier.error),
+            warn: self.warn.saturating_sub(earlier.warn),
+            info: self.info.saturating_sub(earlier.info),
+            debug: self.debug.saturating_sub(earlier.debug),
+            trace: self.trace.saturating_sub(earlier.trace),
+        }
+    }
+}
+
+fn index(level: &Level) -> usize {
+    crate::utils::level_to_index(level)
+}
+
+pub fn log_counts() -> LogCounts {
+    let count = |level: Level| COUNTS[index(&level)].load(Ordering::Relaxed);
+    LogCounts {
+        error: count(Level::ERROR),
+        warn: count(Level::WARN),
+        info: count(Level::INFO),
+        debug: count(Level::DEBUG),
+        trace: count(Level::TRACE),
+    }
+}
+
+/// The first few ERROR and WARN messages, in the order they were logged.
+pub fn first_messages() -> Vec<(Level, String)> {
+    MESSAGES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+pub fn reset() {
+    for count in &COUNTS {
+        count.store(0, Ordering::Relaxed);
+    }
+    MESSAGES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+/// Renders "Finished with N errors, M warnings" followed by the kept messages.
+pub fn render() -> String {
+    let counts = log_counts();
+    let messages = first_messages();
+
+    let mut summary = format!(
+        "Finished with {}, {}",
+        plural(counts.error, "error"),
+        plural(counts.warn, "warning")
+    );
+
+    for (level, message) in &messages {
+        let _ = write!(summary, "\n  [{level}] {message}");
+    }
+
+    let hidden = (counts.error + counts.warn).saturating_sub(messages.len() as u64);
+    if hidden > 0 {
+        let _ = write!(summary, "\n  ... and {hidden} more");
+    }
+
+    summary
+}
+
+pub fn print_summary() {
+    eprintln!("{}", render());
+}
+
+/// Prints the summary only if any errors or warnings were logged.
+pub(crate) fn print_summary_if_needed() {
+    let counts = log_counts();
+    if counts.error > 0 || counts.warn > 0 {
+        print_summary();
+    }
+}
+
+fn plural(count: u64, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+pub struct SummaryLayer {
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl SummaryLayer {
+    pub fn new(redactor: Option<Arc<Redactor>>) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SummaryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let level = *event.metadata().level();
+        COUNTS[index(&level)].fetch_add(1, Ordering::Relaxed);
+
+        if level > Level::WARN {
+            return;
+        }
+
+        // Recording may format values that log themselves, so no lock is held here.
+        let message = LogRecord::from_event(event, self.redactor.as_deref()).message;
+
+        let mut messages = MESSAGES.lock().unwrap_or_else(PoisonError::into_inner);
+        if messages.iter().filter(|(kept, _)| *kept == level).count() >= MAX_MESSAGES_PER_LEVEL {
+            return;
+        }
+
+        messages.push((level, message));
+    }
+}
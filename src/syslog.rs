@@ -0,0 +1,239 @@
+use crate::context::LogContext;
+use crate::formatter::FieldCollector;
+use crate::redact::Redactor;
+
+use chrono::{SecondsFormat, Utc};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, format::Writer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Structured data ID for event fields, using the documentation enterprise number.
+const SD_ID: &str = "fields@32473";
+const NILVALUE: &str = "-";
+const MAX_PARAM_NAME: usize = 32;
+
+/// Formats events as RFC 5424 syslog messages. Event fields other than `message` and
+/// the current [`LogContext`] go into a single structured data element.
+#[derive(Clone)]
+pub struct SyslogFormatter {
+    config: Arc<SyslogFormatterConfig>,
+}
+
+#[derive(Debug, Clone)]
+struct SyslogFormatterConfig {
+    facility: u8,
+    hostname: String,
+    app_name: String,
+    proc_id: String,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl Default for SyslogFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyslogFormatter {
+    pub fn new() -> Self {
+        Self {
+            config: Arc::new(SyslogFormatterConfig {
+                facility: 1,
+                hostname: default_hostname(),
+                app_name: crate::file::app_name().to_string(),
+                proc_id: std::process::id().to_string(),
+                redactor: None,
+            }),
+        }
+    }
+
+    /// Sets the syslog facility code, e.g. 1 for `user` or 16 for `local0`.
+    pub fn with_facility(mut self, facility: u8) -> Self {
+        Arc::make_mut(&mut self.config).facility = facility.min(23);
+        self
+    }
+
+    pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config).hostname = hostname.into();
+        self
+    }
+
+    pub fn with_app_name(mut self, app_name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config).app_name = app_name.into();
+        self
+    }
+
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        Arc::make_mut(&mut self.config).redactor = Some(redactor);
+        self
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for SyslogFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut visitor = FieldCollector::new();
+        event.record(&mut visitor);
+        if let Some(redactor) = &self.config.redactor {
+            visitor.redact(redactor);
+        }
+
+        let priority = u16::from(self.config.facility) * 8 + u16::from(severity(metadata.level()));
+        write!(
+            writer,
+            "<{}>1 {} {} {} {} {} ",
+            priority,
+            Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            header_value(&self.config.hostname, 255),
+            header_value(&self.config.app_name, 48),
+            header_value(&self.config.proc_id, 128),
+            NILVALUE
+        )?;
+
+        write!(writer, "[{SD_ID} target=\"")?;
+        write_param_value(&mut writer, metadata.target())?;
+        writer.write_char('"')?;
+        for (name, value) in visitor.fields.iter().filter(|(name, _)| *name != "message") {
+            write!(writer, " {}=\"", param_name(name))?;
+            write_param_value(&mut writer, value)?;
+            writer.write_char('"')?;
+        }
+        // Event fields win over context fields of the same name.
+        for (key, value) in LogContext::current().fields() {
+            if !visitor.fields.iter().any(|(name, _)| name == key) {
+                let value = match &self.config.redactor {
+                    Some(redactor) => redactor.redact_field(key, Cow::Borrowed(value)),
+                    None => Cow::Borrowed(value.as_str()),
+                };
+                write!(writer, " {}=\"", param_name(key))?;
+                write_param_value(&mut writer, &value)?;
+                writer.write_char('"')?;
+            }
+        }
+        writer.write_char(']')?;
+
+        if let Some((_, message)) = visitor.fields.iter().find(|(name, _)| *name == "message") {
+            write!(writer, " {message}")?;
+        }
+
+        writeln!(writer)
+    }
+}
+
+/// Maps tracing levels onto syslog severities; DEBUG and TRACE share `debug`.
+pub fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+pub(crate) fn default_hostname() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+        })
+        .unwrap_or_else(|| NILVALUE.to_string())
+}
+
+/// Header fields are printable US-ASCII without spaces, truncated to `max_len`.
+fn header_value(value: &str, max_len: usize) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+
+    if value.is_empty() {
+        NILVALUE.to_string()
+    } else {
+        value
+    }
+}
+
+/// PARAM-NAME excludes `=`, space, `]` and `"`, is at most 32 characters and cannot
+/// be empty.
+fn param_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"'))
+        .take(MAX_PARAM_NAME)
+        .collect();
+
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name
+    }
+}
+
+fn write_param_value(writer: &mut impl fmt::Write, value: &str) -> fmt::Result {
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            writer.write_char('\\')?;
+        }
+        writer.write_char(c)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::format_events;
+
+    fn formatter() -> SyslogFormatter {
+        SyslogFormatter::new()
+            .with_facility(16)
+            .with_hostname("db 1")
+            .with_app_name("app")
+    }
+
+    #[test]
+    fn writes_fields_and_context_as_structured_data() {
+        let output = format_events(formatter(), || {
+            LogContext::current()
+                .with("request_id", "42")
+                .with("user", "ann")
+                .scope(|| tracing::warn!(target: "db", user = "bob", query = "a\"]", "slow"));
+        });
+
+        let pid = std::process::id();
+        assert!(output.starts_with("<132>1 "), "{output}");
+        assert!(output.contains(&format!(" db1 app {pid} - ")), "{output}");
+        assert!(
+            output.ends_with(
+                r#"[fields@32473 target="db" user="bob" query="a\"\]" request_id="42"] slow
+"#
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn param_names_are_never_empty() {
+        assert_eq!(param_name("a b=c"), "abc");
+        assert_eq!(param_name("=\"]"), "_");
+        assert_eq!(param_name(&"x".repeat(40)).len(), MAX_PARAM_NAME);
+    }
+}
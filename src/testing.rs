@@ -0,0 +1,244 @@
+//! Captures log output in tests without going through stdout. Requires the `testing`
+//! feature, usually enabled from `[dev-dependencies]`.
+//!
+//! ```ignore
+//! let logs = LogCapture::start();
+//! log_recoverable_error(&eyre!("disk full"), "retrying");
+//! logs.assert_logged(Level::WARN, "disk full");
+//! logs.assert_field("recovery", "retrying");
+//! ```
+
+use crate::config::{LoggingConfig, console_formatter, json_formatter, parse_filter};
+use crate::error::ConfigError;
+use crate::memory::LogRecord;
+use crate::redact::Redactor;
+
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::dispatcher::DefaultGuard;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::{self, format::JsonFields};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+/// Written in place of the time in captured console output.
+pub const FIXED_TIME: &str = "00:00:00";
+/// Written in place of the time in captured JSON output.
+pub const FIXED_TIMESTAMP: &str = "1970-01-01T00:00:00.000000Z";
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap_or_else(PoisonError::into_inner)).into_owned()
+    }
+
+    fn clear(&self) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Records every event logged on the current thread while it is alive, and renders
+/// them with the console and JSON formatting that [`crate::config::init_logging`]
+/// uses, minus colors and with fixed timestamps and no thread ids.
+///
+/// The subscriber is the thread's default, so events from other threads, including
+/// other tokio worker threads, are not captured.
+pub struct LogCapture {
+    records: Arc<Mutex<Vec<LogRecord>>>,
+    console: Buffer,
+    json: Buffer,
+    _guard: DefaultGuard,
+}
+
+impl LogCapture {
+    /// Captures events at every level.
+    pub fn start() -> Self {
+        let config = LoggingConfig {
+            verbose_mode: true,
+            ..LoggingConfig::default()
+        };
+        Self::from_config(&config).expect("the default logging config is valid")
+    }
+
+    /// Uses the filter, timestamps, JSON options and redaction from `config`. The
+    /// outputs and sinks it enables are ignored, as are `BAAD_LOG` and `RUST_LOG`.
+    pub fn from_config(config: &LoggingConfig) -> Result<Self, ConfigError> {
+        let filter = match &config.filter {
+            Some(directives) => parse_filter(directives, config.default_level())?,
+            None => EnvFilter::new(config.default_level().to_string()),
+        };
+        let redactor = config.redactor()?;
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let console = Buffer::default();
+        let json = Buffer::default();
+
+        let console_writer = console.clone();
+        let console_layer = fmt::layer()
+            .with_writer(move || console_writer.clone())
+            .with_ansi(false)
+            .event_format(
+                console_formatter(config, redactor.as_ref()).with_fixed_timestamp(FIXED_TIME),
+            );
+
+        let json_writer = json.clone();
+        let json_layer = fmt::layer()
+            .with_writer(move || json_writer.clone())
+            .with_ansi(false)
+            .with_span_events(config.json.span_events.clone())
+            .fmt_fields(JsonFields::new())
+            .event_format(
+                json_formatter(&config.json, redactor.as_ref())
+                    .with_thread_ids(false)
+                    .with_fixed_timestamp(FIXED_TIMESTAMP),
+            );
+
+        let subscriber = tracing_subscriber::registry()
+            .with(filter)
+            .with(console_layer)
+            .with(json_layer)
+            .with(CaptureLayer {
+                records: records.clone(),
+                redactor,
+            });
+
+        Ok(Self {
+            records,
+            console,
+            json,
+            _guard: tracing::subscriber::set_default(subscriber),
+        })
+    }
+
+    /// Captured events in the order they were logged, including the fields of the
+    /// [`LogContext`](crate::context::LogContext) they were logged in.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn console_output(&self) -> String {
+        self.console.contents()
+    }
+
+    pub fn json_output(&self) -> String {
+        self.json.contents()
+    }
+
+    pub fn clear(&self) {
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.console.clear();
+        self.json.clear();
+    }
+
+    /// The first event at `level` whose message contains `text`.
+    pub fn find(&self, level: Level, text: &str) -> Option<LogRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|record| record.level == level && record.message.contains(text))
+            .cloned()
+    }
+
+    pub fn contains(&self, level: Level, text: &str) -> bool {
+        self.find(level, text).is_some()
+    }
+
+    #[track_caller]
+    pub fn assert_logged(&self, level: Level, text: &str) {
+        if !self.contains(level, text) {
+            panic!(
+                "expected an event at {level} containing {text:?}, captured:\n{}",
+                self.console_output()
+            );
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_not_logged(&self, level: Level, text: &str) {
+        if self.contains(level, text) {
+            panic!(
+                "expected no event at {level} containing {text:?}, captured:\n{}",
+                self.console_output()
+            );
+        }
+    }
+
+    /// Asserts that some event has the field `name` rendered as `value`.
+    #[track_caller]
+    pub fn assert_field(&self, name: &str, value: &str) {
+        let found = self
+            .records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .any(|record| record.fields.iter().any(|(n, v)| n == name && v == value));
+
+        if !found {
+            panic!(
+                "expected an event with {name}={value:?}, captured:\n{}",
+                self.console_output()
+            );
+        }
+    }
+}
+
+struct CaptureLayer {
+    records: Arc<Mutex<Vec<LogRecord>>>,
+    redactor: Option<Arc<Redactor>>,
+}
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Recording may format values that log themselves, so no lock is held here.
+        let record = LogRecord::from_event(event, self.redactor.as_deref());
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(record);
+    }
+}
+
+/// Renders the events `log` emits on the current thread with `formatter`. Span fields
+/// are recorded as JSON, as the JSON output expects.
+#[cfg(test)]
+pub(crate) fn format_events<F>(formatter: F, log: impl FnOnce()) -> String
+where
+    F: fmt::FormatEvent<tracing_subscriber::Registry, JsonFields> + Send + Sync + 'static,
+{
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let layer = fmt::layer()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .fmt_fields(JsonFields::new())
+        .event_format(formatter);
+
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), log);
+    buffer.contents()
+}
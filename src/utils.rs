@@ -1,7 +1,8 @@
 use eyre::Result;
 use lazy_regex::regex;
 use std::future::Future;
-use tracing::{error, Level};
+use std::process::ExitCode;
+use tracing::Level;
 
 #[inline]
 pub fn contains_url(value: &str) -> bool {
@@ -70,33 +71,54 @@ pub fn get_level_visual_length(level: &Level, is_success: bool) -> usize {
     }
 }
 
-pub fn run<F>(f: F)
+pub fn run<F>(f: F) -> ExitCode
 where
     F: FnOnce() -> Result<()>,
 {
-    if let Err(e) = crate::config::init_logging_default() {
-        error!("Failed to initialize logging: {}", e);
-        std::process::exit(1);
-    }
+    let _guard = match crate::config::init_logging_default() {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("Failed to initialize logging: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    if let Err(e) = f() {
-        error!("{:?}", e);
-        std::process::exit(1);
-    }
+    finish(f())
 }
 
-pub async fn run_async<F, Fut>(f: F)
+pub async fn run_async<F, Fut>(f: F) -> ExitCode
 where
     F: FnOnce() -> Fut,
     Fut: Future<Output = Result<()>>,
 {
-    if let Err(e) = crate::config::init_logging_default() {
-        error!("Failed to initialize logging: {}", e);
-        std::process::exit(1);
-    }
+    let _guard = match crate::config::init_logging_default() {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("Failed to initialize logging: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    if let Err(e) = f().await {
-        error!("{:?}", e);
-        std::process::exit(1);
-    }
+    finish(f().await)
+}
+
+fn finish(result: Result<()>) -> ExitCode {
+    let code = match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // The eyre hook logs the error chain while rendering and leaves nothing
+            // behind; without it the rendered report is logged instead.
+            let report = format!("{:?}", e);
+            if !report.is_empty() {
+                crate::error!("{}", report);
+            }
+            ExitCode::FAILURE
+        }
+    };
+
+    crate::config::flush_logging();
+    crate::summary::print_summary_if_needed();
+    crate::config::shutdown_logging();
+
+    code
 }
@@ -0,0 +1,10 @@
+use baad_core::error::install;
+
+#[test]
+fn install_reports_the_first_result_every_time() {
+    // Another hook is in place, so installing ours fails, now and on every retry.
+    eyre::set_hook(Box::new(eyre::DefaultHandler::default_with)).unwrap();
+
+    assert!(install().is_err());
+    assert!(install().is_err());
+}
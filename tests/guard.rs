@@ -0,0 +1,60 @@
+use baad_core::config::{LogOutput, LoggingConfig, init_logging, shutdown_logging};
+use baad_core::error::ConfigError;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn logging_outlives_the_guard_until_shutdown() {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let config = LoggingConfig {
+        console_output: LogOutput::custom(move || writer.clone()),
+        ..LoggingConfig::default()
+    };
+
+    drop(init_logging(config.clone()).unwrap());
+    tracing::info!("logged after the guard was dropped");
+    assert!(
+        buffer
+            .contents()
+            .contains("logged after the guard was dropped"),
+        "{}",
+        buffer.contents()
+    );
+    assert!(matches!(
+        init_logging(config.clone()),
+        Err(ConfigError::LoggingInitFailed)
+    ));
+
+    shutdown_logging();
+    tracing::info!("logged after shutdown");
+    assert!(!buffer.contents().contains("logged after shutdown"));
+
+    let _guard = init_logging(config).unwrap();
+    tracing::info!("logged after initializing again");
+    assert!(
+        buffer
+            .contents()
+            .contains("logged after initializing again")
+    );
+}
@@ -0,0 +1,52 @@
+#![cfg(unix)]
+
+use baad_core::config::{
+    LoggingConfig, NetworkTransport, SyslogConfig, flush_logging, init_logging,
+};
+use baad_core::network::dropped_messages;
+use std::os::unix::net::UnixDatagram;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn syslog_drops_messages_while_unreachable_and_reconnects() {
+    // Connecting to a socket path that does not exist yet fails straight away.
+    let path = std::env::temp_dir().join(format!("baad-syslog-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let config = LoggingConfig {
+        syslog: Some(SyslogConfig {
+            transport: NetworkTransport::Unix(path.clone()),
+            ..SyslogConfig::default()
+        }),
+        ..LoggingConfig::default()
+    };
+    let _guard = init_logging(config).unwrap();
+
+    let dropped = dropped_messages();
+    tracing::info!("sent while unreachable");
+    flush_logging();
+    assert_eq!(dropped_messages() - dropped, 1);
+
+    let receiver = UnixDatagram::bind(&path).unwrap();
+    receiver.set_nonblocking(true).unwrap();
+
+    // Keep logging until the writer's backoff has passed and it reconnects.
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let mut buf = [0; 1024];
+    let received = loop {
+        tracing::info!("sent after reconnecting");
+        flush_logging();
+        if let Ok(len) = receiver.recv(&mut buf) {
+            break String::from_utf8_lossy(&buf[..len]).into_owned();
+        }
+        assert!(Instant::now() < deadline, "the writer never reconnected");
+        thread::sleep(Duration::from_millis(20));
+    };
+    let _ = std::fs::remove_file(&path);
+
+    assert!(
+        received.contains("sent after reconnecting"),
+        "received {received:?}"
+    );
+}
@@ -0,0 +1,244 @@
+#![cfg(feature = "otlp")]
+
+use baad_core::config::{LoggingConfig, OtlpConfig, RedactionConfig, flush_logging, init_logging};
+use baad_core::memory::{self, LogQuery};
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::KeyValue;
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use opentelemetry_proto::tonic::logs::v1::LogRecord;
+use opentelemetry_proto::tonic::trace::v1::Span;
+use prost::Message;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Stands in for a collector's OTLP/HTTP receiver, passing on each request's path and
+/// body.
+fn start_receiver() -> (String, Receiver<(String, Vec<u8>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let sender = sender.clone();
+            thread::spawn(move || serve(stream, sender));
+        }
+    });
+
+    (endpoint, receiver)
+}
+
+fn serve(stream: TcpStream, sender: Sender<(String, Vec<u8>)>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        let _ = sender.send((path, body));
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-type: application/x-protobuf\r\ncontent-length: 0\r\n\r\n")
+            .unwrap();
+    }
+}
+
+#[derive(Default)]
+struct Exported {
+    spans: Vec<Span>,
+    logs: Vec<LogRecord>,
+}
+
+impl Exported {
+    /// Collects requests until `done` is satisfied or the wait times out.
+    fn collect(
+        receiver: &Receiver<(String, Vec<u8>)>,
+        done: impl Fn(&Exported) -> bool,
+    ) -> Exported {
+        let mut exported = Exported::default();
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        while !done(&exported) {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let Ok((path, body)) = receiver.recv_timeout(timeout) else {
+                break;
+            };
+
+            match path.as_str() {
+                "/v1/traces" => {
+                    let request = ExportTraceServiceRequest::decode(body.as_slice()).unwrap();
+                    exported.spans.extend(
+                        request
+                            .resource_spans
+                            .into_iter()
+                            .flat_map(|resource| resource.scope_spans)
+                            .flat_map(|scope| scope.spans),
+                    );
+                }
+                "/v1/logs" => {
+                    let request = ExportLogsServiceRequest::decode(body.as_slice()).unwrap();
+                    exported.logs.extend(
+                        request
+                            .resource_logs
+                            .into_iter()
+                            .flat_map(|resource| resource.scope_logs)
+                            .flat_map(|scope| scope.log_records),
+                    );
+                }
+                other => panic!("unexpected export to {other}"),
+            }
+        }
+
+        exported
+    }
+
+    fn span(&self, name: &str) -> Option<&Span> {
+        self.spans.iter().find(|span| span.name == name)
+    }
+
+    fn log(&self, body: &str) -> Option<&LogRecord> {
+        self.logs.iter().find(|record| {
+            record.body.as_ref().and_then(|value| value.value.as_ref())
+                == Some(&Value::StringValue(body.to_string()))
+        })
+    }
+}
+
+fn attribute<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a Value> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.key == key)
+        .and_then(|attribute| attribute.value.as_ref())
+        .and_then(|value| value.value.as_ref())
+}
+
+#[test]
+fn spans_and_logs_are_correlated() {
+    let (endpoint, receiver) = start_receiver();
+    let config = LoggingConfig {
+        otlp: Some(OtlpConfig {
+            endpoint: Some(endpoint),
+            ..OtlpConfig::default()
+        }),
+        redaction: Some(RedactionConfig::default()),
+        memory_buffer: Some(1000),
+        verbose_mode: true,
+        ..LoggingConfig::default()
+    };
+    let _guard = init_logging(config).unwrap();
+
+    let request = tracing::info_span!("request");
+    let request_context = request.context().span().span_context().clone();
+    assert!(
+        request_context.is_valid(),
+        "OpenTelemetrySpanExt::context found no span context"
+    );
+    request.in_scope(|| tracing::info!("handled"));
+    tracing::info!(parent: &request, "handled with an explicit parent");
+    drop(request);
+
+    let remote_trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+    let remote_parent = SpanContext::new(
+        remote_trace_id,
+        SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    );
+    let job = tracing::info_span!("job");
+    job.set_parent(opentelemetry::Context::new().with_remote_span_context(remote_parent))
+        .unwrap();
+    job.in_scope(|| tracing::info!("job ran"));
+    drop(job);
+
+    let login = tracing::info_span!("login", password = "hunter2");
+    login.in_scope(|| tracing::info!(token = "abc123", "signed in"));
+    drop(login);
+
+    flush_logging();
+    let exported = Exported::collect(&receiver, |exported| {
+        exported.span("request").is_some()
+            && exported.span("job").is_some()
+            && exported.span("login").is_some()
+            && exported.logs.len() >= 4
+    });
+
+    let trace_id = request_context.trace_id().to_bytes().to_vec();
+    let span_id = request_context.span_id().to_bytes().to_vec();
+    let span = exported.span("request").expect("request span exported");
+    assert_eq!(span.trace_id, trace_id);
+    assert_eq!(span.span_id, span_id);
+
+    for body in ["handled", "handled with an explicit parent"] {
+        let record = exported
+            .log(body)
+            .unwrap_or_else(|| panic!("{body:?} exported"));
+        assert_eq!(record.trace_id, trace_id, "trace id of {body:?}");
+        assert_eq!(record.span_id, span_id, "span id of {body:?}");
+    }
+
+    let job = exported.span("job").expect("job span exported");
+    assert_eq!(job.trace_id, remote_trace_id.to_bytes().to_vec());
+    let record = exported.log("job ran").expect("\"job ran\" exported");
+    assert_eq!(record.trace_id, job.trace_id);
+    assert_eq!(record.span_id, job.span_id);
+
+    let redacted = Value::StringValue("[REDACTED]".to_string());
+    let login = exported.span("login").expect("login span exported");
+    assert_eq!(attribute(&login.attributes, "password"), Some(&redacted));
+    let event = login
+        .events
+        .iter()
+        .find(|event| event.name == "signed in")
+        .expect("\"signed in\" recorded on the login span");
+    assert_eq!(attribute(&event.attributes, "token"), Some(&redacted));
+    let record = exported.log("signed in").expect("\"signed in\" exported");
+    assert_eq!(attribute(&record.attributes, "token"), Some(&redacted));
+
+    let records = memory::query_logs(&LogQuery::default());
+    assert!(records.iter().any(|record| record.message == "signed in"));
+    let exporter_records: Vec<_> = records
+        .iter()
+        .filter(|record| {
+            ["hyper", "reqwest"]
+                .iter()
+                .any(|t| record.target.starts_with(t))
+        })
+        .map(|record| record.to_line())
+        .collect();
+    assert!(
+        exporter_records.is_empty(),
+        "exporter events reached other outputs: {exporter_records:#?}"
+    );
+}
@@ -0,0 +1,138 @@
+use baad_core::config::{LoggingConfig, RedactionConfig};
+use baad_core::context::LogContext;
+use baad_core::testing::{FIXED_TIME, FIXED_TIMESTAMP, LogCapture};
+use std::thread;
+use tracing::Level;
+
+#[test]
+fn captures_only_while_alive_and_on_its_own_thread() {
+    tracing::info!("before");
+
+    let logs = LogCapture::start();
+    tracing::info!("during");
+    thread::spawn(|| tracing::info!("other thread"))
+        .join()
+        .unwrap();
+
+    let messages: Vec<String> = logs
+        .records()
+        .into_iter()
+        .map(|record| record.message)
+        .collect();
+    assert_eq!(messages, ["during"]);
+
+    drop(logs);
+    let logs = LogCapture::start();
+    tracing::info!("after");
+    logs.assert_not_logged(Level::INFO, "during");
+    logs.assert_logged(Level::INFO, "after");
+}
+
+#[test]
+fn matches_level_and_message_text() {
+    let logs = LogCapture::start();
+    tracing::warn!("disk almost full");
+    tracing::debug!(retries = 3, "giving up");
+
+    logs.assert_logged(Level::WARN, "almost full");
+    logs.assert_not_logged(Level::ERROR, "almost full");
+    logs.assert_logged(Level::DEBUG, "giving up");
+    assert!(!logs.contains(Level::INFO, "giving up"));
+
+    let record = logs.find(Level::DEBUG, "giving").unwrap();
+    assert_eq!(record.fields, [("retries".to_string(), "3".to_string())]);
+
+    logs.clear();
+    assert!(logs.records().is_empty());
+    assert!(logs.console_output().is_empty());
+    logs.assert_not_logged(Level::WARN, "almost full");
+}
+
+#[test]
+fn matches_event_and_context_fields() {
+    let logs = LogCapture::start();
+    LogContext::current()
+        .with("job_id", 7)
+        .with("asset", "a.png")
+        .scope(|| tracing::info!(asset = "b.png", attempt = 2, "loaded"));
+
+    logs.assert_field("attempt", "2");
+    logs.assert_field("job_id", "7");
+    logs.assert_field("asset", "b.png");
+
+    let record = logs.find(Level::INFO, "loaded").unwrap();
+    assert!(!record.fields.iter().any(|(_, value)| value == "a.png"));
+}
+
+#[test]
+fn renders_console_and_json_with_fixed_timestamps() {
+    let logs = LogCapture::start();
+    tracing::info!(user = "ana", "signed in");
+
+    let console = logs.console_output();
+    assert!(console.starts_with(FIXED_TIME), "{console:?}");
+    assert!(console.contains("signed in"), "{console:?}");
+
+    let json: serde_json::Value = serde_json::from_str(logs.json_output().trim()).unwrap();
+    assert_eq!(json["timestamp"], FIXED_TIMESTAMP);
+    assert_eq!(json["fields"]["message"], "signed in");
+    assert_eq!(json["fields"]["user"], "ana");
+}
+
+#[test]
+fn applies_the_config_filter_and_redaction() {
+    let config = LoggingConfig {
+        filter: Some("warn".to_string()),
+        redaction: Some(RedactionConfig {
+            fields: vec!["token".to_string()],
+            ..RedactionConfig::default()
+        }),
+        ..LoggingConfig::default()
+    };
+    let logs = LogCapture::from_config(&config).unwrap();
+    tracing::info!("filtered out");
+    tracing::warn!(token = "s3cret", "refreshing");
+
+    logs.assert_not_logged(Level::INFO, "filtered out");
+    logs.assert_field("token", "[REDACTED]");
+    assert!(!logs.console_output().contains("s3cret"));
+    assert!(!logs.json_output().contains("s3cret"));
+}
+
+#[test]
+#[should_panic(expected = "expected an event at ERROR containing \"missing\", captured:\n")]
+fn assert_logged_reports_the_captured_output() {
+    let logs = LogCapture::start();
+    tracing::info!("present");
+    logs.assert_logged(Level::ERROR, "missing");
+}
+
+#[test]
+#[should_panic(expected = "expected no event at INFO containing \"present\", captured:\n")]
+fn assert_not_logged_reports_the_captured_output() {
+    let logs = LogCapture::start();
+    tracing::info!("present");
+    logs.assert_not_logged(Level::INFO, "present");
+}
+
+#[test]
+#[should_panic(expected = "expected an event with attempt=\"3\", captured:\n")]
+fn assert_field_reports_the_captured_output() {
+    let logs = LogCapture::start();
+    tracing::info!(attempt = 2, "retrying");
+    logs.assert_field("attempt", "3");
+}
+
+#[test]
+fn failure_messages_include_the_console_output() {
+    let logs = LogCapture::start();
+    tracing::info!("present");
+
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        logs.assert_logged(Level::ERROR, "missing")
+    }))
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.ends_with(&logs.console_output()), "{message:?}");
+    assert!(message.contains("present"), "{message:?}");
+}